@@ -0,0 +1,48 @@
+CREATE TABLE sensors (
+    id   SERIAL PRIMARY KEY,
+    name VARCHAR NOT NULL
+);
+
+CREATE TABLE measurements_type (
+    id    SERIAL PRIMARY KEY,
+    name  VARCHAR NOT NULL,
+    units VARCHAR NOT NULL
+);
+
+CREATE TABLE sensors_measurements (
+    sensor_id          INTEGER NOT NULL REFERENCES sensors (id),
+    type_id            INTEGER NOT NULL REFERENCES measurements_type (id),
+    measurment_formula VARCHAR NOT NULL DEFAULT 'value',
+    PRIMARY KEY (sensor_id, type_id)
+);
+
+CREATE TABLE meteostations (
+    id        SERIAL PRIMARY KEY,
+    name      VARCHAR NOT NULL,
+    longitude NUMERIC NOT NULL,
+    latitude  NUMERIC NOT NULL
+);
+
+CREATE SEQUENCE meteostations_sensors_inventory_number_seq;
+
+CREATE TABLE meteostations_sensors (
+    inventory_number VARCHAR PRIMARY KEY DEFAULT nextval('meteostations_sensors_inventory_number_seq')::text,
+    station_id       INTEGER NOT NULL REFERENCES meteostations (id),
+    sensor_id        INTEGER NOT NULL REFERENCES sensors (id),
+    added_ts         TIMESTAMP DEFAULT now(),
+    removed_ts       TIMESTAMP
+);
+
+ALTER SEQUENCE meteostations_sensors_inventory_number_seq OWNED BY meteostations_sensors.inventory_number;
+
+CREATE INDEX meteostations_sensors_station_id_idx ON meteostations_sensors (station_id);
+CREATE INDEX meteostations_sensors_sensor_id_idx ON meteostations_sensors (sensor_id);
+
+CREATE TABLE measurements (
+    sensor_inventory_number VARCHAR NOT NULL REFERENCES meteostations_sensors (inventory_number),
+    value                   NUMERIC NOT NULL,
+    ts                      TIMESTAMP NOT NULL,
+    type                    INTEGER REFERENCES measurements_type (id)
+);
+
+CREATE INDEX measurements_ts_inventory_number_idx ON measurements (ts, sensor_inventory_number);
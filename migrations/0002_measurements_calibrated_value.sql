@@ -0,0 +1 @@
+ALTER TABLE measurements ADD COLUMN calibrated_value NUMERIC;
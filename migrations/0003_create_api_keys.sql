@@ -0,0 +1,8 @@
+CREATE TABLE api_keys (
+    id         SERIAL PRIMARY KEY,
+    name       VARCHAR NOT NULL,
+    key_hash   VARCHAR NOT NULL UNIQUE,
+    scopes     TEXT[] NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMP
+);
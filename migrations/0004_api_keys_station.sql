@@ -0,0 +1,2 @@
+-- Device tokens: a key bound to a station may only ingest readings for sensors assigned to it.
+ALTER TABLE api_keys ADD COLUMN station_id INTEGER REFERENCES meteostations (id) ON DELETE CASCADE;
@@ -0,0 +1,3 @@
+-- Monotonic reading ids, used as event ids of the live measurement stream.
+ALTER TABLE measurements ADD COLUMN id BIGSERIAL;
+CREATE UNIQUE INDEX measurements_id_idx ON measurements (id);
@@ -0,0 +1,36 @@
+CREATE TABLE alert_rules (
+    id                   SERIAL PRIMARY KEY,
+    name                 VARCHAR NOT NULL,
+    station_id           INTEGER REFERENCES meteostations (id) ON DELETE CASCADE,
+    sensor_id            INTEGER REFERENCES sensors (id) ON DELETE CASCADE,
+    type_id              INTEGER NOT NULL REFERENCES measurements_type (id) ON DELETE CASCADE,
+    operator             TEXT NOT NULL CHECK (operator IN ('gt', 'gte', 'lt', 'lte')),
+    threshold            NUMERIC NOT NULL,
+    hysteresis           NUMERIC NOT NULL DEFAULT 0 CHECK (hysteresis >= 0),
+    min_duration_seconds INTEGER NOT NULL DEFAULT 0 CHECK (min_duration_seconds >= 0),
+    enabled              BOOLEAN NOT NULL DEFAULT TRUE
+);
+
+-- Evaluation progress per rule and sensor: the last reading seen and, while the condition
+-- holds but the alert is not open yet, when the breach started.
+CREATE TABLE alert_states (
+    rule_id                 INTEGER NOT NULL REFERENCES alert_rules (id) ON DELETE CASCADE,
+    sensor_inventory_number VARCHAR NOT NULL REFERENCES meteostations_sensors (inventory_number) ON DELETE CASCADE,
+    last_ts                 TIMESTAMP NOT NULL,
+    breach_started_ts       TIMESTAMP,
+    breach_started_value    NUMERIC,
+    PRIMARY KEY (rule_id, sensor_inventory_number)
+);
+
+CREATE TABLE alerts (
+    id                      SERIAL PRIMARY KEY,
+    rule_id                 INTEGER NOT NULL REFERENCES alert_rules (id) ON DELETE CASCADE,
+    sensor_inventory_number VARCHAR NOT NULL REFERENCES meteostations_sensors (inventory_number) ON DELETE CASCADE,
+    opened_ts               TIMESTAMP NOT NULL,
+    opened_value            NUMERIC NOT NULL,
+    closed_ts               TIMESTAMP,
+    closed_value            NUMERIC
+);
+
+CREATE UNIQUE INDEX alerts_open_idx ON alerts (rule_id, sensor_inventory_number) WHERE closed_ts IS NULL;
+CREATE INDEX alerts_opened_ts_idx ON alerts (opened_ts);
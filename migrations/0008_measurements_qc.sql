@@ -0,0 +1,13 @@
+-- Plausibility limits per measurement type, in calibrated units. Empty limits skip the check.
+ALTER TABLE measurements_type
+    ADD COLUMN qc_min            NUMERIC,
+    ADD COLUMN qc_max            NUMERIC,
+    ADD COLUMN qc_max_step       NUMERIC CHECK (qc_max_step > 0),
+    ADD COLUMN qc_flatline_count INTEGER CHECK (qc_flatline_count >= 2);
+
+-- Readings stored before QC existed are considered good.
+ALTER TABLE measurements
+    ADD COLUMN qc_flag   TEXT NOT NULL DEFAULT 'good' CHECK (qc_flag IN ('good', 'suspect', 'bad')),
+    ADD COLUMN qc_checks TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX measurements_sensor_type_ts_idx ON measurements (sensor_inventory_number, type, ts);
@@ -0,0 +1,23 @@
+-- Manual review of readings. The recorded value is never changed; a correction is stored
+-- next to it, in calibrated units.
+ALTER TABLE measurements
+    ADD COLUMN corrected_value NUMERIC,
+    ADD COLUMN qc_note         TEXT,
+    ADD COLUMN reviewed_by     TEXT,
+    ADD COLUMN reviewed_at     TIMESTAMP;
+
+-- Every review, with the flag and correction it replaced.
+CREATE TABLE measurement_reviews (
+    id                       BIGSERIAL PRIMARY KEY,
+    measurement_id           BIGINT    NOT NULL REFERENCES measurements (id) ON DELETE CASCADE,
+    previous_qc_flag         TEXT      NOT NULL,
+    previous_corrected_value NUMERIC,
+    qc_flag                  TEXT      NOT NULL CHECK (qc_flag IN ('good', 'suspect', 'bad')),
+    corrected_value          NUMERIC,
+    note                     TEXT,
+    api_key_id               INTEGER   REFERENCES api_keys (id) ON DELETE SET NULL,
+    reviewed_by              TEXT      NOT NULL,
+    reviewed_at              TIMESTAMP NOT NULL DEFAULT now()
+);
+
+CREATE INDEX measurement_reviews_measurement_idx ON measurement_reviews (measurement_id);
@@ -0,0 +1,5 @@
+-- Physical quantity a measurement type records, used to compute derived variables from
+-- co-located readings. Values are expected in °C, % and m/s.
+ALTER TABLE measurements_type
+    ADD COLUMN quantity TEXT UNIQUE
+        CHECK (quantity IN ('air_temperature', 'relative_humidity', 'dew_point', 'wind_speed'));
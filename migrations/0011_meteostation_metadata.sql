@@ -0,0 +1,10 @@
+-- Reporting metadata of a station. The timezone is an IANA name checked against
+-- pg_timezone_names when it is set through the API.
+ALTER TABLE meteostations
+    ADD COLUMN elevation       NUMERIC,
+    ADD COLUMN timezone        TEXT,
+    ADD COLUMN wmo_index       TEXT UNIQUE CHECK (wmo_index ~ '^[0-9]{5}$'),
+    ADD COLUMN icao_code       TEXT UNIQUE CHECK (icao_code ~ '^[A-Z]{4}$'),
+    ADD COLUMN owner           TEXT,
+    ADD COLUMN commissioned_on DATE,
+    ADD COLUMN tags            TEXT[] NOT NULL DEFAULT '{}';
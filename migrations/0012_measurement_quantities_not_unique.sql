@@ -0,0 +1,3 @@
+-- Several types may record one quantity, e.g. air temperature in °C and in °F; derived
+-- variables average them after converting to the units the formulas expect.
+ALTER TABLE measurements_type DROP CONSTRAINT measurements_type_quantity_key;
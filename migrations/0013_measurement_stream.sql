@@ -0,0 +1,20 @@
+-- Positions of readings in the live measurement stream, in commit order. Ingests queue the ids
+-- they store in measurement_stream_pending within their own transaction; a sequencer, one at a
+-- time, moves committed ids into measurement_stream. Positions are therefore handed out in the
+-- order they become visible, and a stream resumed after a position cannot miss a late commit.
+CREATE TABLE measurement_stream_pending (
+    measurement_id BIGINT PRIMARY KEY REFERENCES measurements (id) ON DELETE CASCADE
+);
+
+CREATE TABLE measurement_stream (
+    seq            BIGSERIAL PRIMARY KEY,
+    measurement_id BIGINT NOT NULL UNIQUE REFERENCES measurements (id) ON DELETE CASCADE
+);
+
+-- Existing readings keep their id as position, so Last-Event-ID values issued so far stay valid.
+INSERT INTO measurement_stream (seq, measurement_id) SELECT id, id FROM measurements;
+SELECT setval(
+    pg_get_serial_sequence('measurement_stream', 'seq'),
+    COALESCE((SELECT MAX(seq) FROM measurement_stream), 0) + 1,
+    false
+);
@@ -0,0 +1,129 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertOperator {
+    /// Breached while the value is above the threshold.
+    Gt,
+    Gte,
+    /// Breached while the value is below the threshold.
+    Lt,
+    Lte,
+}
+
+impl AlertOperator {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertOperator::Gt => "gt",
+            AlertOperator::Gte => "gte",
+            AlertOperator::Lt => "lt",
+            AlertOperator::Lte => "lte",
+        }
+    }
+}
+
+impl fmt::Display for AlertOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AlertOperator {
+    type Err = ApiError;
+
+    fn from_str(operator: &str) -> Result<Self, Self::Err> {
+        match operator {
+            "gt" => Ok(AlertOperator::Gt),
+            "gte" => Ok(AlertOperator::Gte),
+            "lt" => Ok(AlertOperator::Lt),
+            "lte" => Ok(AlertOperator::Lte),
+            _ => Err(ApiError::Validation(format!("unknown alert operator {}", operator))),
+        }
+    }
+}
+
+/// Threshold condition of an alert rule.
+pub struct AlertCondition {
+    pub operator: AlertOperator,
+    pub threshold: BigDecimal,
+    /// How far the value has to fall back past the threshold before an open alert closes.
+    pub hysteresis: BigDecimal,
+    /// How long the condition has to hold before the alert opens.
+    pub min_duration: Duration,
+}
+
+impl AlertCondition {
+    pub fn breached(&self, value: &BigDecimal) -> bool {
+        match self.operator {
+            AlertOperator::Gt => *value > self.threshold,
+            AlertOperator::Gte => *value >= self.threshold,
+            AlertOperator::Lt => *value < self.threshold,
+            AlertOperator::Lte => *value <= self.threshold,
+        }
+    }
+
+    pub fn cleared(&self, value: &BigDecimal) -> bool {
+        match self.operator {
+            AlertOperator::Gt | AlertOperator::Gte => *value < &self.threshold - &self.hysteresis,
+            AlertOperator::Lt | AlertOperator::Lte => *value > &self.threshold + &self.hysteresis,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AlertTransition {
+    /// The condition held for the minimum duration; the alert opens at the start of the breach.
+    Opened { ts: NaiveDateTime, value: BigDecimal },
+    Closed { ts: NaiveDateTime, value: BigDecimal },
+}
+
+/// Evaluation state of one rule for one sensor.
+#[derive(Default)]
+pub struct AlertTracker {
+    pub last_ts: Option<NaiveDateTime>,
+    /// Start and value of a breach that has not lasted long enough to open an alert yet.
+    pub breach_started: Option<(NaiveDateTime, BigDecimal)>,
+    pub open: bool,
+}
+
+impl AlertTracker {
+    /// Feeds the next reading; readings not newer than the last one seen are ignored.
+    pub fn observe(&mut self, condition: &AlertCondition, ts: NaiveDateTime, value: &BigDecimal) -> Option<AlertTransition> {
+        if self.last_ts.is_some_and(|last_ts| ts <= last_ts) {
+            return None;
+        }
+        self.last_ts = Some(ts);
+
+        if self.open {
+            if condition.cleared(value) {
+                self.open = false;
+                return Some(AlertTransition::Closed { ts, value: value.clone() });
+            }
+            return None;
+        }
+
+        if !condition.breached(value) {
+            self.breach_started = None;
+            return None;
+        }
+
+        let (started_ts, started_value) = self.breach_started.get_or_insert_with(|| (ts, value.clone()));
+        if ts - *started_ts < condition.min_duration {
+            return None;
+        }
+
+        let transition = AlertTransition::Opened { ts: *started_ts, value: started_value.clone() };
+        self.breach_started = None;
+        self.open = true;
+
+        Some(transition)
+    }
+}
@@ -0,0 +1,239 @@
+use std::fmt;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, ResponseError};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+pub const API_KEY_HEADER: &str = "X-API-Key";
+const API_KEY_PREFIX: &str = "mk_";
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Read any resource.
+    Read,
+    /// Ingest measurements.
+    Write,
+    /// Everything, including changes to stations, sensors and measurement types.
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = ApiError;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(ApiError::Validation(format!("unknown scope {}", scope))),
+        }
+    }
+}
+
+/// The API key that authenticated the current request, available as an extractor.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub key_id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Station a device token is bound to, `None` for keys that are not tied to a station.
+    pub station_id: Option<i32>,
+}
+
+impl Identity {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&Scope::Admin) || self.scopes.contains(&scope)
+    }
+}
+
+impl FromRequest for Identity {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        ready(
+            req.extensions()
+                .get::<Identity>()
+                .cloned()
+                .ok_or_else(|| ApiError::Unauthorized(String::from("missing API key"))),
+        )
+    }
+}
+
+/// Scopes a request may be authorized by, any one of them suffices; `None` for the public
+/// Swagger UI and preflight requests. `path` must be the percent-decoded path the router
+/// matches on, otherwise `/%61pi/...` would slip past as a Swagger UI path.
+pub fn required_scope(method: &Method, path: &str) -> Option<&'static [Scope]> {
+    if method == Method::OPTIONS || path.starts_with("/api-doc/") || !path.starts_with("/api") {
+        return None;
+    }
+
+    // Any key may inspect itself, and devices that only ingest still open the socket.
+    if path == "/api/api_keys/me" || path == "/api/measurements/ws" {
+        return Some(&[Scope::Read, Scope::Write]);
+    }
+    if path.starts_with("/api/api_keys") || path.starts_with("/api/webhooks") {
+        return Some(&[Scope::Admin]);
+    }
+
+    match *method {
+        Method::GET | Method::HEAD => Some(&[Scope::Read]),
+        Method::POST if path == "/api/measurements" || path == "/api/measurements/import" => Some(&[Scope::Write]),
+        // Write keys belong to ingesting devices; QC reviews overwrite what they sent, so they stay with admins.
+        Method::POST if path == "/api/measurements/reviews" => Some(&[Scope::Admin]),
+        _ => Some(&[Scope::Admin]),
+    }
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn generate_api_key() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    let secret: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", API_KEY_PREFIX, secret)
+}
+
+pub async fn authenticate(pool: &PgPool, key: &str) -> Result<Identity, ApiError> {
+    let row = query!(
+        "SELECT id, name, scopes, station_id FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+        hash_api_key(key)
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized(String::from("invalid API key")))?;
+
+    Ok(Identity {
+        key_id: row.id,
+        name: row.name,
+        scopes: row.scopes.iter().filter_map(|scope| scope.parse().ok()).collect(),
+        station_id: row.station_id,
+    })
+}
+
+/// Registers the key from `ADMIN_API_KEY` as an admin key so a fresh database can be administered.
+pub async fn bootstrap_admin_key(pool: &PgPool, key: &str) -> Result<(), ApiError> {
+    query!(
+        "INSERT INTO api_keys (name, key_hash, scopes) VALUES ('bootstrap', $1, '{admin}')
+         ON CONFLICT (key_hash) DO NOTHING",
+        hash_api_key(key)
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Middleware checking the `X-API-Key` header against `api_keys` and the scope the route requires.
+pub struct ApiKeyAuth;
+
+impl<S, B> Transform<S, ServiceRequest> for ApiKeyAuth
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = ApiKeyAuthMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyAuthMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct ApiKeyAuthMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for ApiKeyAuthMiddleware<S>
+    where
+        S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+        B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            // Routing runs on the decoded path, so authorize that one rather than the raw URI.
+            let scopes = match required_scope(req.method(), req.match_info().as_str()) {
+                Some(scopes) => scopes,
+                None => return service.call(req).await.map(ServiceResponse::map_into_left_body),
+            };
+
+            match authorize(&req, scopes).await {
+                Ok(identity) => {
+                    req.extensions_mut().insert(identity);
+                    service.call(req).await.map(ServiceResponse::map_into_left_body)
+                }
+                Err(err) => {
+                    let response = err.error_response();
+                    Ok(req.into_response(response).map_into_right_body())
+                }
+            }
+        })
+    }
+}
+
+async fn authorize(req: &ServiceRequest, scopes: &[Scope]) -> Result<Identity, ApiError> {
+    let key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ApiError::Unauthorized(String::from("missing API key")))?;
+
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .ok_or_else(|| ApiError::Internal(String::from("database pool is not configured")))?;
+
+    let identity = authenticate(pool.get_ref(), key).await?;
+
+    if !scopes.iter().any(|scope| identity.allows(*scope)) {
+        let names: Vec<&str> = scopes.iter().map(Scope::as_str).collect();
+        return Err(ApiError::Forbidden(format!("API key lacks the {} scope", names.join(" or "))));
+    }
+
+    Ok(identity)
+}
@@ -1,6 +1,78 @@
 use std::env;
+use std::fmt;
+use std::time::Duration;
 use dotenv::dotenv;
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
 use sqlx::postgres::PgPoolOptions;
+use sqlx::{query, query_scalar, PgPool};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 50_000;
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+/// Limits applied to `POST /api/measurements`.
+#[derive(Clone)]
+pub struct IngestConfig {
+    pub max_batch_size: usize,
+    pub max_payload_bytes: usize,
+}
+
+impl IngestConfig {
+    pub fn from_env() -> IngestConfig {
+        dotenv().ok();
+        let max_batch_size = env::var("MEASUREMENTS_MAX_BATCH_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_MAX_BATCH_SIZE);
+        let max_payload_bytes = env::var("MEASUREMENTS_MAX_PAYLOAD_BYTES")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_MAX_PAYLOAD_BYTES);
+
+        IngestConfig { max_batch_size, max_payload_bytes }
+    }
+}
+
+pub const DEFAULT_WEBHOOK_MAX_ATTEMPTS: i32 = 8;
+pub const DEFAULT_WEBHOOK_RETRY_BASE_SECONDS: u64 = 10;
+pub const DEFAULT_WEBHOOK_TIMEOUT_SECONDS: u64 = 10;
+
+/// Settings of the webhook delivery worker.
+#[derive(Clone)]
+pub struct WebhookConfig {
+    /// Attempts after which a delivery is given up and marked failed.
+    pub max_attempts: i32,
+    /// Delay before the first retry, doubled on every further one.
+    pub retry_base: Duration,
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl WebhookConfig {
+    pub fn from_env() -> WebhookConfig {
+        dotenv().ok();
+        let max_attempts = env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|attempts| attempts.parse().ok())
+            .unwrap_or(DEFAULT_WEBHOOK_MAX_ATTEMPTS);
+        let retry_base = env::var("WEBHOOK_RETRY_BASE_SECONDS")
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(DEFAULT_WEBHOOK_RETRY_BASE_SECONDS);
+        let timeout = env::var("WEBHOOK_TIMEOUT_SECONDS")
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(DEFAULT_WEBHOOK_TIMEOUT_SECONDS);
+
+        WebhookConfig {
+            max_attempts,
+            retry_base: Duration::from_secs(retry_base),
+            timeout: Duration::from_secs(timeout),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
 
 pub async fn get_db_pool() -> sqlx::Result<sqlx::PgPool> {
     dotenv().ok();
@@ -10,3 +82,110 @@ pub async fn get_db_pool() -> sqlx::Result<sqlx::PgPool> {
         .connect(&database_url)
         .await
 }
+
+/// Latest migration version embedded in this binary.
+pub fn expected_schema_version() -> Option<i64> {
+    MIGRATOR.iter().map(|migration| migration.version).max()
+}
+
+/// Latest migration version applied to the database, `None` on an empty database. Only reads,
+/// so it is safe to serve without running migrations.
+pub async fn applied_schema_version(pool: &PgPool) -> Result<Option<i64>, sqlx::Error> {
+    let tracked: bool = query_scalar("SELECT to_regclass('_sqlx_migrations') IS NOT NULL")
+        .fetch_one(pool)
+        .await?;
+
+    if !tracked {
+        return Ok(None);
+    }
+
+    query_scalar("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+        .fetch_one(pool)
+        .await
+}
+
+/// Why the database schema could not be brought up to date.
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The database was migrated by a newer release than this binary.
+    SchemaAhead { applied: i64, expected: i64 },
+    Migrate(MigrateError),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::SchemaAhead { applied, expected } => write!(
+                f,
+                "database schema version {} is ahead of version {} expected by this binary",
+                applied, expected
+            ),
+            MigrationError::Migrate(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<MigrateError> for MigrationError {
+    fn from(err: MigrateError) -> Self {
+        MigrationError::Migrate(err)
+    }
+}
+
+impl From<sqlx::Error> for MigrationError {
+    fn from(err: sqlx::Error) -> Self {
+        MigrationError::Migrate(MigrateError::Execute(err))
+    }
+}
+
+/// Version of the migration creating the tables that databases predating migrations already have.
+const BASELINE_VERSION: i64 = 1;
+
+/// Records the baseline migration as applied on a database created before migrations were
+/// embedded. Such a database has the tables but no migration history, and running the baseline
+/// on it would fail on the first `CREATE TABLE`. Returns whether the schema was adopted.
+pub async fn adopt_existing_schema(pool: &PgPool) -> Result<bool, MigrationError> {
+    let unmigrated: bool = query_scalar(
+        "SELECT to_regclass('_sqlx_migrations') IS NULL AND to_regclass('measurements') IS NOT NULL"
+    )
+        .fetch_one(pool)
+        .await?;
+
+    let baseline = MIGRATOR.iter().find(|migration| migration.version == BASELINE_VERSION);
+    let (true, Some(baseline)) = (unmigrated, baseline) else {
+        return Ok(false);
+    };
+
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+         VALUES ($1, $2, TRUE, $3, 0)
+         ON CONFLICT (version) DO NOTHING"
+    )
+        .bind(baseline.version)
+        .bind(&*baseline.description)
+        .bind(&*baseline.checksum)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(true)
+}
+
+/// Applies pending migrations, refusing to touch a schema that is ahead of the binary.
+/// A database created before migrations were embedded is adopted first.
+pub async fn run_migrations(pool: &PgPool) -> Result<(), MigrationError> {
+    if adopt_existing_schema(pool).await? {
+        log::info!("Adopted the existing database schema as migration {}", BASELINE_VERSION);
+    }
+
+    if let (Some(applied), Some(expected)) = (applied_schema_version(pool).await?, expected_schema_version()) {
+        if applied > expected {
+            return Err(MigrationError::SchemaAhead { applied, expected });
+        }
+    }
+
+    Ok(MIGRATOR.run(pool).await?)
+}
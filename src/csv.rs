@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Quotes `field` when it contains a delimiter, quote or line break (RFC 4180).
+pub fn escape_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Appends one CRLF-terminated record to `out`.
+pub fn write_record<S: AsRef<str>>(out: &mut String, fields: &[S]) {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        out.push_str(&escape_field(field.as_ref()));
+    }
+    out.push_str("\r\n");
+}
+
+/// One record of a CSV document, or why it could not be read.
+#[derive(Debug, PartialEq)]
+pub struct CsvRecord {
+    /// Line the record starts on, counting from 1.
+    pub line: usize,
+    pub fields: Result<Vec<String>, String>,
+}
+
+/// Splits `input` into records (RFC 4180, with any single-character delimiter). Quoted fields
+/// may span lines; blank lines are skipped. A malformed record is reported and parsing resumes
+/// on the next line.
+pub fn parse_records(input: &str, delimiter: char) -> Vec<CsvRecord> {
+    let mut records = Vec::new();
+    let mut chars = input.chars().peekable();
+    let mut line = 1;
+
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut error = None;
+
+        loop {
+            let Some(c) = chars.next() else {
+                if quoted {
+                    error = Some(String::from("unterminated quoted field"));
+                }
+                break;
+            };
+
+            if quoted {
+                match c {
+                    '"' if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    '"' => {
+                        quoted = false;
+                        if chars.peek().is_some_and(|&next| next != delimiter && next != '\r' && next != '\n') {
+                            error.get_or_insert_with(|| String::from("unexpected character after a closing quote"));
+                        }
+                    }
+                    _ => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        field.push(c);
+                    }
+                }
+            } else if c == delimiter {
+                fields.push(std::mem::take(&mut field));
+            } else if c == '\n' {
+                line += 1;
+                break;
+            } else if c == '\r' && matches!(chars.peek(), Some('\n') | None) {
+                continue;
+            } else if c == '"' && field.is_empty() {
+                quoted = true;
+            } else {
+                field.push(c);
+            }
+        }
+        fields.push(field);
+
+        if error.is_none() && fields.len() == 1 && fields[0].trim().is_empty() {
+            continue;
+        }
+
+        records.push(CsvRecord {
+            line: start,
+            fields: match error {
+                Some(error) => Err(error),
+                None => Ok(fields),
+            },
+        });
+    }
+
+    records
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+
+use crate::config::WebhookConfig;
+use crate::error::ApiError;
+use crate::handlers::webhooks::{claim_due_deliveries, record_delivery_result, DueDelivery};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+pub const WEBHOOK_SECRET_PREFIX: &str = "whsec_";
+
+/// Deliveries claimed by the worker per poll.
+const CLAIM_BATCH_SIZE: i64 = 50;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WebhookEvent {
+    /// A batch of readings was stored; large batches arrive as several deliveries.
+    #[serde(rename = "measurement.created")]
+    MeasurementCreated,
+    /// A sensor was removed from its station.
+    #[serde(rename = "sensor.removed")]
+    SensorRemoved,
+    #[serde(rename = "alert.opened")]
+    AlertOpened,
+    #[serde(rename = "alert.closed")]
+    AlertClosed,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::MeasurementCreated => "measurement.created",
+            WebhookEvent::SensorRemoved => "sensor.removed",
+            WebhookEvent::AlertOpened => "alert.opened",
+            WebhookEvent::AlertClosed => "alert.closed",
+        }
+    }
+}
+
+impl fmt::Display for WebhookEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for WebhookEvent {
+    type Err = ApiError;
+
+    fn from_str(event: &str) -> Result<Self, Self::Err> {
+        match event {
+            "measurement.created" => Ok(WebhookEvent::MeasurementCreated),
+            "sensor.removed" => Ok(WebhookEvent::SensorRemoved),
+            "alert.opened" => Ok(WebhookEvent::AlertOpened),
+            "alert.closed" => Ok(WebhookEvent::AlertClosed),
+            _ => Err(ApiError::Validation(format!("unknown webhook event {}", event))),
+        }
+    }
+}
+
+/// Random signing secret for a webhook that was created without one.
+pub fn generate_webhook_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    let secret: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}{}", WEBHOOK_SECRET_PREFIX, secret)
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`, sent as `sha256=<hex>` in `X-Webhook-Signature`.
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Delay before retrying after the `attempts`-th failed attempt: doubling from `base`, capped at an hour.
+pub fn retry_delay(attempts: i32, base: Duration) -> Duration {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    base.saturating_mul(2u32.pow(exponent)).min(MAX_RETRY_DELAY)
+}
+
+/// Posts one signed delivery, returning the response status, or why no response was received.
+pub async fn send_delivery(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    delivery_id: i64,
+    event_type: &str,
+    body: Vec<u8>,
+) -> Result<u16, String> {
+    let timestamp = chrono::Utc::now().timestamp();
+    let signature = sign_payload(secret, timestamp, &body);
+
+    let response = client
+        .post(url)
+        .header("Content-Type", "application/json")
+        .header(EVENT_HEADER, event_type)
+        .header(DELIVERY_HEADER, delivery_id.to_string())
+        .header(TIMESTAMP_HEADER, timestamp.to_string())
+        .header(SIGNATURE_HEADER, format!("sha256={}", signature))
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    Ok(response.status().as_u16())
+}
+
+async fn attempt(pool: &PgPool, client: &reqwest::Client, config: &WebhookConfig, delivery: DueDelivery) {
+    let body = serde_json::to_vec(&delivery.payload).unwrap_or_default();
+    let result = send_delivery(client, &delivery.url, &delivery.secret, delivery.id, &delivery.event_type, body).await;
+
+    let retry_in = retry_delay(delivery.attempts + 1, config.retry_base);
+    if let Err(err) = record_delivery_result(pool, delivery.id, result, config.max_attempts, retry_in).await {
+        log::warn!("Failed to record webhook delivery {}: {}", delivery.id, err);
+    }
+}
+
+/// Drains the webhook outbox for as long as the server runs.
+pub async fn run_worker(pool: PgPool, config: WebhookConfig) {
+    let client = match reqwest::Client::builder().timeout(config.timeout).build() {
+        Ok(client) => client,
+        Err(err) => {
+            log::error!("Webhook delivery is disabled, failed to build HTTP client: {}", err);
+            return;
+        }
+    };
+
+    loop {
+        let due = match claim_due_deliveries(&pool, CLAIM_BATCH_SIZE, config.timeout * 2).await {
+            Ok(due) => due,
+            Err(err) => {
+                log::warn!("Failed to claim webhook deliveries: {}", err);
+                Vec::new()
+            }
+        };
+
+        if due.is_empty() {
+            tokio::time::sleep(config.poll_interval).await;
+            continue;
+        }
+
+        join_all(due.into_iter().map(|delivery| attempt(&pool, &client, &config, delivery))).await;
+    }
+}
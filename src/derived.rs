@@ -0,0 +1,252 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, FromPrimitive};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+use crate::units::Unit;
+
+/// Magnus coefficients over water, valid from -45 °C to 60 °C.
+const MAGNUS_B: f64 = 17.62;
+const MAGNUS_C: f64 = 243.12;
+
+/// Physical quantity recorded by a measurement type.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantity {
+    AirTemperature,
+    RelativeHumidity,
+    DewPoint,
+    WindSpeed,
+}
+
+impl Quantity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Quantity::AirTemperature => "air_temperature",
+            Quantity::RelativeHumidity => "relative_humidity",
+            Quantity::DewPoint => "dew_point",
+            Quantity::WindSpeed => "wind_speed",
+        }
+    }
+
+    /// Units the formulas expect; readings in other units are converted first.
+    pub fn unit(&self) -> Unit {
+        match self {
+            Quantity::AirTemperature | Quantity::DewPoint => Unit::Celsius,
+            Quantity::RelativeHumidity => Unit::Percent,
+            Quantity::WindSpeed => Unit::MetersPerSecond,
+        }
+    }
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Quantity {
+    type Err = ApiError;
+
+    fn from_str(quantity: &str) -> Result<Self, Self::Err> {
+        match quantity {
+            "air_temperature" => Ok(Quantity::AirTemperature),
+            "relative_humidity" => Ok(Quantity::RelativeHumidity),
+            "dew_point" => Ok(Quantity::DewPoint),
+            "wind_speed" => Ok(Quantity::WindSpeed),
+            _ => Err(ApiError::Validation(format!("unknown quantity {}", quantity))),
+        }
+    }
+}
+
+/// Readings of one station at one timestamp, by quantity.
+#[derive(Default, Debug, Clone, Copy)]
+pub struct Conditions {
+    pub air_temperature: Option<f64>,
+    pub relative_humidity: Option<f64>,
+    pub dew_point: Option<f64>,
+    pub wind_speed: Option<f64>,
+}
+
+impl Conditions {
+    pub fn set(&mut self, quantity: Quantity, value: f64) {
+        let slot = match quantity {
+            Quantity::AirTemperature => &mut self.air_temperature,
+            Quantity::RelativeHumidity => &mut self.relative_humidity,
+            Quantity::DewPoint => &mut self.dew_point,
+            Quantity::WindSpeed => &mut self.wind_speed,
+        };
+        *slot = Some(value);
+    }
+
+    /// Measured relative humidity, else the one implied by the dew point.
+    fn humidity(&self) -> Option<f64> {
+        self.relative_humidity
+            .or_else(|| relative_humidity(self.air_temperature?, self.dew_point?))
+    }
+}
+
+/// Variable computed from co-located readings, served as a virtual measurement type with a
+/// negative id so it never collides with a stored type.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DerivedVariable {
+    DewPoint,
+    /// Relative humidity from the dew point, for stations that measure dew point directly.
+    RelativeHumidity,
+    HeatIndex,
+    WindChill,
+    /// Wind chill when cold and windy, heat index when hot, otherwise the air temperature.
+    ApparentTemperature,
+}
+
+impl DerivedVariable {
+    pub const ALL: [DerivedVariable; 5] = [
+        DerivedVariable::DewPoint,
+        DerivedVariable::RelativeHumidity,
+        DerivedVariable::HeatIndex,
+        DerivedVariable::WindChill,
+        DerivedVariable::ApparentTemperature,
+    ];
+
+    pub fn type_id(&self) -> i32 {
+        match self {
+            DerivedVariable::DewPoint => -1,
+            DerivedVariable::RelativeHumidity => -2,
+            DerivedVariable::HeatIndex => -3,
+            DerivedVariable::WindChill => -4,
+            DerivedVariable::ApparentTemperature => -5,
+        }
+    }
+
+    pub fn from_type_id(type_id: i32) -> Option<DerivedVariable> {
+        Self::ALL.into_iter().find(|variable| variable.type_id() == type_id)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DerivedVariable::DewPoint => "dew_point",
+            DerivedVariable::RelativeHumidity => "relative_humidity",
+            DerivedVariable::HeatIndex => "heat_index",
+            DerivedVariable::WindChill => "wind_chill",
+            DerivedVariable::ApparentTemperature => "apparent_temperature",
+        }
+    }
+
+    pub fn units(&self) -> &'static str {
+        match self {
+            DerivedVariable::RelativeHumidity => "%",
+            _ => "°C",
+        }
+    }
+
+    /// Quantities read to compute the variable. Air temperature is always required.
+    pub fn inputs(&self) -> &'static [Quantity] {
+        match self {
+            DerivedVariable::DewPoint => &[Quantity::AirTemperature, Quantity::RelativeHumidity],
+            DerivedVariable::HeatIndex => &[Quantity::AirTemperature, Quantity::RelativeHumidity, Quantity::DewPoint],
+            DerivedVariable::RelativeHumidity => &[Quantity::AirTemperature, Quantity::DewPoint],
+            DerivedVariable::WindChill => &[Quantity::AirTemperature, Quantity::WindSpeed],
+            DerivedVariable::ApparentTemperature => &[
+                Quantity::AirTemperature,
+                Quantity::RelativeHumidity,
+                Quantity::DewPoint,
+                Quantity::WindSpeed,
+            ],
+        }
+    }
+
+    /// Value of the variable, or `None` when an input is missing or it is undefined for the
+    /// conditions, such as wind chill above 10 °C.
+    pub fn compute(&self, conditions: &Conditions) -> Option<f64> {
+        let temperature = conditions.air_temperature?;
+
+        match self {
+            DerivedVariable::DewPoint => dew_point(temperature, conditions.relative_humidity?),
+            DerivedVariable::RelativeHumidity => relative_humidity(temperature, conditions.dew_point?),
+            DerivedVariable::HeatIndex => Some(heat_index(temperature, conditions.humidity()?)),
+            DerivedVariable::WindChill => wind_chill(temperature, conditions.wind_speed?),
+            DerivedVariable::ApparentTemperature => {
+                let chill = conditions.wind_speed.and_then(|speed| wind_chill(temperature, speed));
+                let heat = conditions
+                    .humidity()
+                    .filter(|_| temperature >= 26.7)
+                    .map(|humidity| heat_index(temperature, humidity));
+
+                Some(chill.or(heat).unwrap_or(temperature))
+            }
+        }
+    }
+
+    /// `compute` rounded to hundredths.
+    pub fn compute_decimal(&self, conditions: &Conditions) -> Option<BigDecimal> {
+        let value = self.compute(conditions).filter(|value| value.is_finite())?;
+        BigDecimal::from_f64(value).map(|value| value.round(2))
+    }
+}
+
+impl fmt::Display for DerivedVariable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Dew point in °C by the Magnus formula.
+pub fn dew_point(temperature: f64, relative_humidity: f64) -> Option<f64> {
+    if relative_humidity <= 0.0 {
+        return None;
+    }
+
+    let gamma = (relative_humidity / 100.0).ln() + MAGNUS_B * temperature / (MAGNUS_C + temperature);
+    Some(MAGNUS_C * gamma / (MAGNUS_B - gamma))
+}
+
+/// Relative humidity in % from the dew point, by the Magnus formula.
+pub fn relative_humidity(temperature: f64, dew_point: f64) -> Option<f64> {
+    let ratio = (MAGNUS_B * dew_point / (MAGNUS_C + dew_point) - MAGNUS_B * temperature / (MAGNUS_C + temperature)).exp();
+    Some((100.0 * ratio).min(100.0))
+}
+
+/// Heat index in °C following the US National Weather Service: Steadman's simple formula,
+/// and the Rothfusz regression with its adjustments once that reaches 80 °F.
+pub fn heat_index(temperature: f64, relative_humidity: f64) -> f64 {
+    let t = temperature * 9.0 / 5.0 + 32.0;
+    let rh = relative_humidity;
+
+    let simple = 0.5 * (t + 61.0 + (t - 68.0) * 1.2 + rh * 0.094);
+    let fahrenheit = if (simple + t) / 2.0 < 80.0 {
+        simple
+    } else {
+        let mut index = -42.379 + 2.04901523 * t + 10.14333127 * rh
+            - 0.22475541 * t * rh
+            - 0.00683783 * t * t
+            - 0.05481717 * rh * rh
+            + 0.00122874 * t * t * rh
+            + 0.00085282 * t * rh * rh
+            - 0.00000199 * t * t * rh * rh;
+
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            index -= (13.0 - rh) / 4.0 * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            index += (rh - 85.0) / 10.0 * ((87.0 - t) / 5.0);
+        }
+        index
+    };
+
+    (fahrenheit - 32.0) * 5.0 / 9.0
+}
+
+/// Wind chill in °C by the North American index, defined at or below 10 °C with wind above
+/// 4.8 km/h. Wind speed is in m/s.
+pub fn wind_chill(temperature: f64, wind_speed: f64) -> Option<f64> {
+    let speed = wind_speed * 3.6;
+    if temperature > 10.0 || speed <= 4.8 {
+        return None;
+    }
+
+    let factor = speed.powf(0.16);
+    Some(13.12 + 0.6215 * temperature - 11.37 * factor + 0.3965 * temperature * factor)
+}
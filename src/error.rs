@@ -0,0 +1,172 @@
+use std::fmt;
+
+use actix_web::error::{JsonPayloadError, PayloadError};
+use actix_web::http::StatusCode;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use sqlx::error::ErrorKind;
+use sqlx::migrate::MigrateError;
+
+use crate::formula::FormulaError;
+use crate::models::ProblemDetails;
+
+pub const PROBLEM_CONTENT_TYPE: &str = "application/problem+json";
+
+#[derive(Debug)]
+pub enum ApiError {
+    NotFound(String),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
+    Validation(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    ForeignKeyViolation(String),
+    UniqueViolation(String),
+    Internal(String),
+    /// Failure of one item of a batch request; the whole batch was rolled back.
+    BatchItem { index: usize, source: Box<ApiError> },
+}
+
+impl ApiError {
+    /// Attributes the error to the item at `index` of a batch request.
+    pub fn at_item(self, index: usize) -> ApiError {
+        ApiError::BatchItem { index, source: Box::new(self) }
+    }
+
+    /// Machine-readable problem code returned in the `code` member.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BatchItem { source, .. } => source.code(),
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Validation(_) => "validation_failed",
+            ApiError::PayloadTooLarge(_) => "payload_too_large",
+            ApiError::UnsupportedMediaType(_) => "unsupported_media_type",
+            ApiError::ForeignKeyViolation(_) => "foreign_key_violation",
+            ApiError::UniqueViolation(_) => "unique_violation",
+            ApiError::Internal(_) => "internal_error",
+        }
+    }
+
+    fn detail(&self) -> String {
+        match self {
+            ApiError::NotFound(detail)
+            | ApiError::Unauthorized(detail)
+            | ApiError::Forbidden(detail)
+            | ApiError::Conflict(detail)
+            | ApiError::Validation(detail)
+            | ApiError::PayloadTooLarge(detail)
+            | ApiError::UnsupportedMediaType(detail)
+            | ApiError::ForeignKeyViolation(detail)
+            | ApiError::UniqueViolation(detail) => detail.clone(),
+            ApiError::Internal(_) => String::from("internal server error"),
+            ApiError::BatchItem { index, source } => format!("item {}: {}", index, source.detail()),
+        }
+    }
+
+    /// RFC 7807 body describing the error.
+    pub fn problem(&self) -> ProblemDetails {
+        let status = self.status_code();
+
+        ProblemDetails {
+            r#type: String::from("about:blank"),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: self.detail(),
+            code: self.code().to_string(),
+            item: self.item(),
+        }
+    }
+
+    fn item(&self) -> Option<usize> {
+        match self {
+            ApiError::BatchItem { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Internal(detail) => write!(f, "{}: {}", self.code(), detail),
+            ApiError::BatchItem { index, source } => write!(f, "item {}: {}", index, source),
+            _ => write!(f, "{}: {}", self.code(), self.detail()),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::Conflict(_) | ApiError::ForeignKeyViolation(_) | ApiError::UniqueViolation(_) => StatusCode::CONFLICT,
+            ApiError::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::BatchItem { source, .. } => source.status_code(),
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // The response only says "internal server error"; keep the cause in the server log.
+        if self.status_code().is_server_error() {
+            log::error!("{}", self);
+        }
+
+        HttpResponse::build(self.status_code())
+            .content_type(PROBLEM_CONTENT_TYPE)
+            .json(self.problem())
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::RowNotFound => ApiError::NotFound(String::from("resource not found")),
+            sqlx::Error::Database(db_err) => match db_err.kind() {
+                ErrorKind::ForeignKeyViolation => ApiError::ForeignKeyViolation(db_err.message().to_string()),
+                ErrorKind::UniqueViolation => ApiError::UniqueViolation(db_err.message().to_string()),
+                ErrorKind::NotNullViolation | ErrorKind::CheckViolation => ApiError::Validation(db_err.message().to_string()),
+                _ => ApiError::Internal(err.to_string()),
+            },
+            _ => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl From<MigrateError> for ApiError {
+    fn from(err: MigrateError) -> Self {
+        ApiError::Internal(err.to_string())
+    }
+}
+
+impl From<FormulaError> for ApiError {
+    fn from(err: FormulaError) -> Self {
+        ApiError::Validation(format!("invalid measurement formula: {}", err))
+    }
+}
+
+/// Error handler for the query and path extractors, so malformed input is reported as a problem too.
+pub fn validation_error_handler<E: fmt::Display>(err: E, _req: &HttpRequest) -> actix_web::Error {
+    ApiError::Validation(err.to_string()).into()
+}
+
+/// Error handler for JSON bodies: oversized bodies and other content types keep their 413 and
+/// 415 statuses, anything else is a validation problem.
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::OverflowKnownLength { .. }
+        | JsonPayloadError::Overflow { .. }
+        | JsonPayloadError::Payload(PayloadError::Overflow) => ApiError::PayloadTooLarge(err.to_string()).into(),
+        JsonPayloadError::ContentType => ApiError::UnsupportedMediaType(err.to_string()).into(),
+        _ => ApiError::Validation(err.to_string()).into(),
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::web::Bytes;
+use futures_util::stream::{self, Stream};
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::sync::Notify;
+use tokio::time::{interval, Interval, MissedTickBehavior};
+
+use crate::error::ApiError;
+use crate::handlers::measurements::{fetch_measurement_events, sequence_measurements, SEQUENCE_BATCH_SIZE};
+use crate::models::{Measurement, MeasurementStreamQuery};
+
+/// Events buffered per subscriber before it lags and has to catch up from the database.
+pub const EVENT_CHANNEL_CAPACITY: usize = 4096;
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// How often the sequencer looks for readings queued by other server instances.
+const SEQUENCE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A stored reading together with the station and sensor it was ingested for.
+pub struct MeasurementEvent {
+    pub id: i64,
+    pub station_id: i32,
+    pub sensor_id: i32,
+    pub measurement: Measurement,
+}
+
+impl MeasurementStreamQuery {
+    pub fn matches(&self, event: &MeasurementEvent) -> bool {
+        self.meteostation.is_none_or(|station_id| station_id == event.station_id)
+            && self.sensor.is_none_or(|sensor_id| sensor_id == event.sensor_id)
+            && self.r#type.is_none_or(|type_id| Some(type_id) == event.measurement.r#type)
+    }
+}
+
+/// Fan-out of committed readings to live subscribers.
+#[derive(Clone)]
+pub struct MeasurementEvents {
+    sender: broadcast::Sender<Arc<MeasurementEvent>>,
+    stored: Arc<Notify>,
+}
+
+impl MeasurementEvents {
+    pub fn new() -> MeasurementEvents {
+        let (sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        MeasurementEvents { sender, stored: Arc::new(Notify::new()) }
+    }
+
+    /// Wakes the sequencer after an ingest committed readings.
+    pub fn notify_stored(&self) {
+        self.stored.notify_one();
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<MeasurementEvent>> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, events: Vec<MeasurementEvent>) {
+        for event in events {
+            // Sending only fails when nobody is subscribed.
+            let _ = self.sender.send(Arc::new(event));
+        }
+    }
+}
+
+impl Default for MeasurementEvents {
+    fn default() -> Self {
+        MeasurementEvents::new()
+    }
+}
+
+/// Hands out stream positions to committed readings and publishes them, for as long as the
+/// server runs. Woken by local ingests, and polls for readings stored by other instances.
+pub async fn run_sequencer(pool: PgPool, events: MeasurementEvents) {
+    loop {
+        match sequence_measurements(&pool, events.has_subscribers()).await {
+            Ok((count, sequenced)) => {
+                events.publish(sequenced);
+                if count == SEQUENCE_BATCH_SIZE {
+                    continue;
+                }
+            }
+            Err(err) => log::warn!("Failed to sequence measurements: {}", err),
+        }
+
+        tokio::select! {
+            _ = events.stored.notified() => {}
+            _ = tokio::time::sleep(SEQUENCE_POLL_INTERVAL) => {}
+        }
+    }
+}
+
+struct StreamState {
+    pool: PgPool,
+    receiver: broadcast::Receiver<Arc<MeasurementEvent>>,
+    query: MeasurementStreamQuery,
+    heartbeat: Interval,
+    pending: VecDeque<Arc<MeasurementEvent>>,
+    /// Id of the last event sent, the resume point when catching up from the database.
+    last_id: i64,
+    /// Live events up to this id were already replayed from the database.
+    replayed_until: i64,
+    /// The last replay hit the page limit, so more stored events follow.
+    replay_incomplete: bool,
+}
+
+impl StreamState {
+    async fn replay(&mut self) -> Result<(), ApiError> {
+        let (events, complete) = fetch_measurement_events(&self.pool, &self.query, self.last_id).await?;
+
+        if let Some(last) = events.last() {
+            self.replayed_until = self.replayed_until.max(last.id);
+        }
+        self.replay_incomplete = !complete;
+        self.pending.extend(events.into_iter().map(Arc::new));
+
+        Ok(())
+    }
+}
+
+fn measurement_frame(event: &MeasurementEvent) -> Bytes {
+    let data = serde_json::to_string(&event.measurement).unwrap_or_default();
+    Bytes::from(format!("id: {}\nevent: measurement\ndata: {}\n\n", event.id, data))
+}
+
+fn heartbeat_frame() -> Bytes {
+    Bytes::from_static(b"event: heartbeat\ndata: {}\n\n")
+}
+
+/// Server-sent events for readings committed after `last_event_id`, or from now on without it,
+/// interleaved with heartbeats.
+pub async fn measurement_stream(
+    pool: PgPool,
+    events: &MeasurementEvents,
+    query: MeasurementStreamQuery,
+    last_event_id: Option<i64>,
+) -> Result<impl Stream<Item = Result<Bytes, ApiError>>, ApiError> {
+    // Subscribe before replaying so nothing committed in between is lost.
+    let receiver = events.subscribe();
+
+    let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    heartbeat.reset();
+
+    let mut state = StreamState {
+        pool,
+        receiver,
+        query,
+        heartbeat,
+        pending: VecDeque::new(),
+        last_id: last_event_id.unwrap_or_default(),
+        replayed_until: 0,
+        replay_incomplete: false,
+    };
+
+    if last_event_id.is_some() {
+        state.replay().await?;
+    }
+
+    Ok(stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.pending.pop_front() {
+                state.last_id = state.last_id.max(event.id);
+                return Some((Ok(measurement_frame(&event)), state));
+            }
+
+            if state.replay_incomplete {
+                if let Err(err) = state.replay().await {
+                    return Some((Err(err), state));
+                }
+                continue;
+            }
+
+            tokio::select! {
+                received = state.receiver.recv() => match received {
+                    Ok(event) => {
+                        if event.id > state.replayed_until && state.query.matches(&event) {
+                            state.pending.push_back(event);
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {
+                        if let Err(err) = state.replay().await {
+                            return Some((Err(err), state));
+                        }
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = state.heartbeat.tick() => return Some((Ok(heartbeat_frame()), state)),
+            }
+        }
+    }))
+}
@@ -0,0 +1,451 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, FromPrimitive, ToPrimitive};
+
+pub const DEFAULT_FORMULA: &str = "value";
+
+/// Longest formula accepted, in bytes.
+pub const MAX_FORMULA_LENGTH: usize = 512;
+
+/// Deepest nesting of parentheses, function calls, signs and exponents accepted. Parsing and
+/// evaluation recurse once per level.
+pub const MAX_NESTING_DEPTH: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum FormulaError {
+    UnexpectedChar(char, usize),
+    UnexpectedEnd,
+    UnexpectedToken(String, usize),
+    UnknownIdentifier(String),
+    WrongArity { function: String, expected: usize, found: usize },
+    TooLong,
+    TooDeep,
+    NotFinite,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormulaError::UnexpectedChar(c, pos) => write!(f, "unexpected character '{}' at position {}", c, pos),
+            FormulaError::UnexpectedEnd => write!(f, "unexpected end of formula"),
+            FormulaError::UnexpectedToken(token, pos) => write!(f, "unexpected '{}' at position {}", token, pos),
+            FormulaError::UnknownIdentifier(name) => write!(f, "unknown identifier '{}'", name),
+            FormulaError::WrongArity { function, expected, found } => {
+                write!(f, "function '{}' takes {} argument(s), got {}", function, expected, found)
+            }
+            FormulaError::TooLong => write!(f, "formula is longer than {} characters", MAX_FORMULA_LENGTH),
+            FormulaError::TooDeep => write!(f, "formula is nested deeper than {} levels", MAX_NESTING_DEPTH),
+            FormulaError::NotFinite => write!(f, "formula result is not a finite number"),
+        }
+    }
+}
+
+impl std::error::Error for FormulaError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(usize, usize),
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Caret,
+    LParen,
+    RParen,
+    Comma,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Sin,
+    Cos,
+    Tan,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max,
+    Pow,
+}
+
+impl Function {
+    fn lookup(name: &str) -> Option<Function> {
+        Some(match name {
+            "abs" => Function::Abs,
+            "sqrt" => Function::Sqrt,
+            "exp" => Function::Exp,
+            "ln" => Function::Ln,
+            "log10" => Function::Log10,
+            "sin" => Function::Sin,
+            "cos" => Function::Cos,
+            "tan" => Function::Tan,
+            "floor" => Function::Floor,
+            "ceil" => Function::Ceil,
+            "round" => Function::Round,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            "pow" => Function::Pow,
+            _ => return None,
+        })
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Function::Min | Function::Max | Function::Pow => 2,
+            _ => 1,
+        }
+    }
+
+    fn apply(self, args: &[f64]) -> f64 {
+        match self {
+            Function::Abs => args[0].abs(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Exp => args[0].exp(),
+            Function::Ln => args[0].ln(),
+            Function::Log10 => args[0].log10(),
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Tan => args[0].tan(),
+            Function::Floor => args[0].floor(),
+            Function::Ceil => args[0].ceil(),
+            Function::Round => args[0].round(),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+            Function::Pow => args[0].powf(args[1]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Value,
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, value: f64) -> f64 {
+        match self {
+            Expr::Number(n) => *n,
+            Expr::Value => value,
+            Expr::Neg(inner) => -inner.eval(value),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(value), rhs.eval(value));
+                match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::Mul => lhs * rhs,
+                    BinaryOp::Div => lhs / rhs,
+                    BinaryOp::Pow => lhs.powf(rhs),
+                }
+            }
+            Expr::Call(function, args) => {
+                let args: Vec<f64> = args.iter().map(|arg| arg.eval(value)).collect();
+                function.apply(&args)
+            }
+        }
+    }
+}
+
+/// Arithmetic expression over the raw reading `value`, e.g. `0.5 * value + 3` or `sqrt(abs(value))`.
+///
+/// Supports `+ - * / ^`, unary minus, parentheses, the constants `pi` and `e`
+/// and the functions `abs sqrt exp ln log10 sin cos tan floor ceil round min max pow`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    expr: Expr,
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Formula, FormulaError> {
+        if source.len() > MAX_FORMULA_LENGTH {
+            return Err(FormulaError::TooLong);
+        }
+
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { source, tokens, pos: 0, depth: 0 };
+        let expr = parser.expression()?;
+
+        match parser.tokens.get(parser.pos) {
+            Some((_, offset)) => Err(parser.unexpected(*offset)),
+            None => Ok(Formula { expr }),
+        }
+    }
+
+    pub fn eval(&self, value: f64) -> Result<f64, FormulaError> {
+        let result = self.expr.eval(value);
+        if result.is_finite() {
+            Ok(result)
+        } else {
+            Err(FormulaError::NotFinite)
+        }
+    }
+
+    pub fn apply(&self, value: &BigDecimal) -> Result<BigDecimal, FormulaError> {
+        let raw = value.to_f64().ok_or(FormulaError::NotFinite)?;
+        let result = self.eval(raw)?;
+
+        BigDecimal::from_f64(result)
+            .map(|calibrated| calibrated.normalized())
+            .ok_or(FormulaError::NotFinite)
+    }
+}
+
+impl FromStr for Formula {
+    type Err = FormulaError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Formula::parse(source)
+    }
+}
+
+/// Checks every formula of a sensor request, `None` meaning the default formula.
+/// Returns the index of the first invalid formula with its error.
+pub fn validate_formulas<'a, I>(formulas: I) -> Result<(), (usize, FormulaError)>
+    where
+        I: IntoIterator<Item = Option<&'a str>>,
+{
+    for (index, formula) in formulas.into_iter().enumerate() {
+        if let Some(formula) = formula {
+            Formula::parse(formula).map_err(|err| (index, err))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, FormulaError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let c = bytes[pos] as char;
+        let start = pos;
+
+        let token = match c {
+            ' ' | '\t' | '\n' | '\r' => {
+                pos += 1;
+                continue;
+            }
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '*' => Token::Star,
+            '/' => Token::Slash,
+            '^' => Token::Caret,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            ',' => Token::Comma,
+            '0'..='9' | '.' => {
+                while pos < bytes.len() && (bytes[pos].is_ascii_digit() || bytes[pos] == b'.') {
+                    pos += 1;
+                }
+                if pos < bytes.len() && (bytes[pos] == b'e' || bytes[pos] == b'E') {
+                    let mut exp = pos + 1;
+                    if exp < bytes.len() && (bytes[exp] == b'+' || bytes[exp] == b'-') {
+                        exp += 1;
+                    }
+                    if exp < bytes.len() && bytes[exp].is_ascii_digit() {
+                        pos = exp;
+                        while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                            pos += 1;
+                        }
+                    }
+                }
+                let number = source[start..pos]
+                    .parse::<f64>()
+                    .map_err(|_| FormulaError::UnexpectedToken(source[start..pos].to_string(), start))?;
+                tokens.push((Token::Number(number), start));
+                continue;
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                    pos += 1;
+                }
+                tokens.push((Token::Ident(start, pos), start));
+                continue;
+            }
+            _ => {
+                let c = source[start..].chars().next().unwrap_or(c);
+                return Err(FormulaError::UnexpectedChar(c, start));
+            }
+        };
+
+        tokens.push((token, start));
+        pos += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).map(|(token, _)| *token)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize), FormulaError> {
+        let token = self.tokens.get(self.pos).copied().ok_or(FormulaError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn unexpected(&self, offset: usize) -> FormulaError {
+        let text: String = self.source[offset..]
+            .chars()
+            .take_while(|c| !c.is_whitespace())
+            .take(16)
+            .collect();
+        FormulaError::UnexpectedToken(text, offset)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), FormulaError> {
+        let (token, offset) = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(self.unexpected(offset))
+        }
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn expression(&mut self) -> Result<Expr, FormulaError> {
+        let mut lhs = self.term()?;
+        while let Some(op) = match self.peek() {
+            Some(Token::Plus) => Some(BinaryOp::Add),
+            Some(Token::Minus) => Some(BinaryOp::Sub),
+            _ => None,
+        } {
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut lhs = self.unary()?;
+        while let Some(op) = match self.peek() {
+            Some(Token::Star) => Some(BinaryOp::Mul),
+            Some(Token::Slash) => Some(BinaryOp::Div),
+            _ => None,
+        } {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // unary := '-' unary | '+' unary | power
+    //
+    // Every nested subexpression is parsed through here, so this is where the depth is bounded.
+    fn unary(&mut self) -> Result<Expr, FormulaError> {
+        if self.depth == MAX_NESTING_DEPTH {
+            return Err(FormulaError::TooDeep);
+        }
+        self.depth += 1;
+
+        let expr = match self.peek() {
+            Some(Token::Minus) => {
+                self.pos += 1;
+                self.unary().map(|inner| Expr::Neg(Box::new(inner)))
+            }
+            Some(Token::Plus) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        };
+
+        self.depth -= 1;
+        expr
+    }
+
+    // power := primary ('^' unary)?   (right associative, binds tighter than unary minus on the left)
+    fn power(&mut self) -> Result<Expr, FormulaError> {
+        let base = self.primary()?;
+        if self.peek() == Some(Token::Caret) {
+            self.pos += 1;
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(BinaryOp::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    // primary := number | identifier | identifier '(' args ')' | '(' expression ')'
+    fn primary(&mut self) -> Result<Expr, FormulaError> {
+        let (token, offset) = self.next()?;
+        match token {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::LParen => {
+                let inner = self.expression()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Token::Ident(start, end) => {
+                let name = &self.source[start..end];
+                if self.peek() == Some(Token::LParen) {
+                    self.pos += 1;
+                    return self.call(name);
+                }
+                match name {
+                    "value" => Ok(Expr::Value),
+                    "pi" => Ok(Expr::Number(std::f64::consts::PI)),
+                    "e" => Ok(Expr::Number(std::f64::consts::E)),
+                    _ => Err(FormulaError::UnknownIdentifier(name.to_string())),
+                }
+            }
+            _ => Err(self.unexpected(offset)),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expr, FormulaError> {
+        let function = Function::lookup(name).ok_or_else(|| FormulaError::UnknownIdentifier(name.to_string()))?;
+
+        let mut args = Vec::new();
+        if self.peek() != Some(Token::RParen) {
+            args.push(self.expression()?);
+            while self.peek() == Some(Token::Comma) {
+                self.pos += 1;
+                args.push(self.expression()?);
+            }
+        }
+        self.expect(Token::RParen)?;
+
+        if args.len() != function.arity() {
+            return Err(FormulaError::WrongArity {
+                function: name.to_string(),
+                expected: function.arity(),
+                found: args.len(),
+            });
+        }
+
+        Ok(Expr::Call(function, args))
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{HashMap, HashSet};
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDateTime};
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder, query, query_as};
+use crate::alerting::{AlertCondition, AlertTracker, AlertTransition};
+use crate::delivery::WebhookEvent;
+use crate::error::ApiError;
+use crate::handlers::webhooks::enqueue_webhook_event;
+use crate::models::{Alert, AlertQuery, AlertRule, AlertRuleRequest, AlertStatus};
+
+/// A stored reading as seen by alert evaluation.
+pub struct AlertReading<'a> {
+    pub inventory_number: &'a str,
+    pub station_id: i32,
+    pub sensor_id: i32,
+    pub type_id: i32,
+    pub ts: NaiveDateTime,
+    /// Calibrated value, or the raw one when the reading could not be calibrated.
+    pub value: BigDecimal,
+}
+
+impl AlertRule {
+    fn condition(&self) -> AlertCondition {
+        AlertCondition {
+            operator: self.operator,
+            threshold: self.threshold.clone(),
+            hysteresis: self.hysteresis.clone(),
+            min_duration: Duration::seconds(self.min_duration_seconds.into()),
+        }
+    }
+
+    fn applies_to(&self, reading: &AlertReading) -> bool {
+        self.type_id == reading.type_id
+            && self.station_id.is_none_or(|station_id| station_id == reading.station_id)
+            && self.sensor_id.is_none_or(|sensor_id| sensor_id == reading.sensor_id)
+    }
+}
+
+pub async fn fetch_alert_rules(pool: &PgPool) -> Result<Vec<AlertRule>, ApiError> {
+    let rows = query!(
+        "SELECT id, name, station_id, sensor_id, type_id, operator, threshold, hysteresis,
+                min_duration_seconds, enabled
+         FROM alert_rules ORDER BY id"
+    )
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter()
+        .map(|row| {
+            Ok(AlertRule {
+                id: row.id,
+                name: row.name,
+                station_id: row.station_id,
+                sensor_id: row.sensor_id,
+                type_id: row.type_id,
+                operator: row.operator.parse()?,
+                threshold: row.threshold,
+                hysteresis: row.hysteresis,
+                min_duration_seconds: row.min_duration_seconds,
+                enabled: row.enabled,
+            })
+        })
+        .collect()
+}
+
+pub async fn insert_alert_rule(pool: &PgPool, item: &AlertRuleRequest) -> Result<AlertRule, ApiError> {
+    let hysteresis = item.hysteresis.clone().unwrap_or_default();
+    let min_duration_seconds = item.min_duration_seconds.unwrap_or_default();
+    let enabled = item.enabled.unwrap_or(true);
+
+    let id = query!(
+        "INSERT INTO alert_rules
+            (name, station_id, sensor_id, type_id, operator, threshold, hysteresis, min_duration_seconds, enabled)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+         RETURNING id",
+        item.name,
+        item.station_id,
+        item.sensor_id,
+        item.type_id,
+        item.operator.as_str(),
+        item.threshold,
+        hysteresis,
+        min_duration_seconds,
+        enabled
+    )
+        .fetch_one(pool)
+        .await?
+        .id;
+
+    Ok(AlertRule {
+        id,
+        name: item.name.clone(),
+        station_id: item.station_id,
+        sensor_id: item.sensor_id,
+        type_id: item.type_id,
+        operator: item.operator,
+        threshold: item.threshold.clone(),
+        hysteresis,
+        min_duration_seconds,
+        enabled,
+    })
+}
+
+/// Replaces a rule. Pending breaches are forgotten and re-evaluated against the new condition;
+/// alerts that are already open stay open until the new condition clears them.
+pub async fn update_one_alert_rule(pool: &PgPool, rule_id: i32, item: &AlertRuleRequest) -> Result<AlertRule, ApiError> {
+    let hysteresis = item.hysteresis.clone().unwrap_or_default();
+    let min_duration_seconds = item.min_duration_seconds.unwrap_or_default();
+    let enabled = item.enabled.unwrap_or(true);
+
+    let mut tx = pool.begin().await?;
+
+    let result = query!(
+        "UPDATE alert_rules
+         SET name = $1, station_id = $2, sensor_id = $3, type_id = $4, operator = $5, threshold = $6,
+             hysteresis = $7, min_duration_seconds = $8, enabled = $9
+         WHERE id = $10",
+        item.name,
+        item.station_id,
+        item.sensor_id,
+        item.type_id,
+        item.operator.as_str(),
+        item.threshold,
+        hysteresis,
+        min_duration_seconds,
+        enabled,
+        rule_id
+    )
+        .execute(&mut *tx)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("alert rule {} not found", rule_id)));
+    }
+
+    query!("UPDATE alert_states SET breach_started_ts = NULL, breach_started_value = NULL WHERE rule_id = $1", rule_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(AlertRule {
+        id: rule_id,
+        name: item.name.clone(),
+        station_id: item.station_id,
+        sensor_id: item.sensor_id,
+        type_id: item.type_id,
+        operator: item.operator,
+        threshold: item.threshold.clone(),
+        hysteresis,
+        min_duration_seconds,
+        enabled,
+    })
+}
+
+pub async fn delete_one_alert_rule(pool: &PgPool, rule_id: i32) -> Result<(), ApiError> {
+    let result = query!("DELETE FROM alert_rules WHERE id = $1", rule_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("alert rule {} not found", rule_id)));
+    }
+
+    Ok(())
+}
+
+pub async fn fetch_alerts(pool: &PgPool, alert_query: &AlertQuery) -> Result<Vec<Alert>, ApiError> {
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"
+        SELECT a.id, a.rule_id, ms.station_id, a.sensor_inventory_number,
+               a.opened_ts, a.opened_value, a.closed_ts, a.closed_value
+        FROM alerts a
+        JOIN meteostations_sensors ms ON ms.inventory_number = a.sensor_inventory_number
+        WHERE TRUE"#,
+    );
+
+    match alert_query.status {
+        Some(AlertStatus::Open) => { builder.push(" AND a.closed_ts IS NULL"); }
+        Some(AlertStatus::Closed) => { builder.push(" AND a.closed_ts IS NOT NULL"); }
+        None => {}
+    }
+    if let Some(rule_id) = alert_query.rule {
+        builder.push(" AND a.rule_id = ").push_bind(rule_id);
+    }
+    if let Some(station_id) = alert_query.meteostation {
+        builder.push(" AND ms.station_id = ").push_bind(station_id);
+    }
+    if let Some(inventory_number) = &alert_query.inventory_number {
+        builder.push(" AND a.sensor_inventory_number = ").push_bind(inventory_number);
+    }
+
+    builder.push(" ORDER BY a.opened_ts DESC, a.id DESC");
+
+    let alerts = builder
+        .build_query_as::<Alert>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(alerts)
+}
+
+/// Runs the enabled rules over freshly stored readings, in timestamp order per sensor, and
+/// records opened and closed alerts, queueing their webhooks. Runs inside the ingesting transaction.
+pub async fn evaluate_alerts(conn: &mut PgConnection, readings: &[AlertReading<'_>]) -> Result<(), ApiError> {
+    let type_ids: Vec<i32> = readings.iter().map(|r| r.type_id).collect::<HashSet<_>>().into_iter().collect();
+
+    let rules: Vec<AlertRule> = query!(
+        "SELECT id, name, station_id, sensor_id, type_id, operator, threshold, hysteresis,
+                min_duration_seconds, enabled
+         FROM alert_rules WHERE enabled AND type_id = ANY($1)",
+        &type_ids
+    )
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .filter_map(|row| {
+            Some(AlertRule {
+                id: row.id,
+                name: row.name,
+                station_id: row.station_id,
+                sensor_id: row.sensor_id,
+                type_id: row.type_id,
+                operator: row.operator.parse().ok()?,
+                threshold: row.threshold,
+                hysteresis: row.hysteresis,
+                min_duration_seconds: row.min_duration_seconds,
+                enabled: row.enabled,
+            })
+        })
+        .collect();
+
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let mut series: HashMap<(i32, &str), Vec<&AlertReading>> = HashMap::new();
+    for reading in readings {
+        for rule in rules.iter().filter(|rule| rule.applies_to(reading)) {
+            series.entry((rule.id, reading.inventory_number)).or_default().push(reading);
+        }
+    }
+
+    if series.is_empty() {
+        return Ok(());
+    }
+
+    let rule_ids: Vec<i32> = rules.iter().map(|rule| rule.id).collect();
+    let inventory_numbers: Vec<String> = series.keys().map(|(_, inv)| inv.to_string()).collect::<HashSet<_>>().into_iter().collect();
+
+    // Concurrent ingests of one sensor would both read the same state and race to open the
+    // alert; serialise them per rule and sensor until the transaction ends. Locks are taken in
+    // key order so two batches cannot deadlock.
+    let (lock_rule_ids, lock_inventory_numbers): (Vec<i32>, Vec<String>) =
+        series.keys().map(|(rule_id, inv)| (*rule_id, inv.to_string())).unzip();
+
+    query(
+        "SELECT pg_advisory_xact_lock(k.rule_id, hashtext(k.inventory_number))
+         FROM (
+             SELECT rule_id, inventory_number
+             FROM UNNEST($1::integer[], $2::varchar[]) AS u(rule_id, inventory_number)
+             ORDER BY rule_id, hashtext(inventory_number)
+         ) k"
+    )
+        .bind(lock_rule_ids)
+        .bind(lock_inventory_numbers)
+        .execute(&mut *conn)
+        .await?;
+
+    let mut trackers: HashMap<(i32, String), AlertTracker> = HashMap::new();
+
+    let states = query!(
+        "SELECT rule_id, sensor_inventory_number, last_ts, breach_started_ts, breach_started_value
+         FROM alert_states WHERE rule_id = ANY($1) AND sensor_inventory_number = ANY($2)",
+        &rule_ids,
+        &inventory_numbers
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for state in states {
+        let tracker = trackers.entry((state.rule_id, state.sensor_inventory_number)).or_default();
+        tracker.last_ts = Some(state.last_ts);
+        tracker.breach_started = state.breach_started_ts.zip(state.breach_started_value);
+    }
+
+    let open_alerts = query!(
+        "SELECT rule_id, sensor_inventory_number FROM alerts
+         WHERE closed_ts IS NULL AND rule_id = ANY($1) AND sensor_inventory_number = ANY($2)",
+        &rule_ids,
+        &inventory_numbers
+    )
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for alert in open_alerts {
+        trackers.entry((alert.rule_id, alert.sensor_inventory_number)).or_default().open = true;
+    }
+
+    let rules: HashMap<i32, AlertRule> = rules.into_iter().map(|rule| (rule.id, rule)).collect();
+    let mut transitions = Vec::new();
+
+    for ((rule_id, inventory_number), mut readings) in series {
+        let condition = rules[&rule_id].condition();
+        let tracker = trackers.entry((rule_id, inventory_number.to_string())).or_default();
+
+        readings.sort_by_key(|reading| reading.ts);
+        for reading in readings {
+            if let Some(transition) = tracker.observe(&condition, reading.ts, &reading.value) {
+                transitions.push((rule_id, inventory_number, transition));
+            }
+        }
+    }
+
+    let mut state_rule_ids = Vec::with_capacity(trackers.len());
+    let mut state_inventory_numbers = Vec::with_capacity(trackers.len());
+    let mut last_timestamps = Vec::with_capacity(trackers.len());
+    let mut breach_timestamps = Vec::with_capacity(trackers.len());
+    let mut breach_values = Vec::with_capacity(trackers.len());
+
+    for ((rule_id, inventory_number), tracker) in trackers {
+        if let Some(last_ts) = tracker.last_ts {
+            state_rule_ids.push(rule_id);
+            state_inventory_numbers.push(inventory_number);
+            last_timestamps.push(last_ts);
+            breach_timestamps.push(tracker.breach_started.as_ref().map(|(ts, _)| *ts));
+            breach_values.push(tracker.breach_started.map(|(_, value)| value));
+        }
+    }
+
+    query(
+        "INSERT INTO alert_states (rule_id, sensor_inventory_number, last_ts, breach_started_ts, breach_started_value)
+         SELECT * FROM UNNEST($1::integer[], $2::varchar[], $3::timestamp[], $4::timestamp[], $5::numeric[])
+         ON CONFLICT (rule_id, sensor_inventory_number) DO UPDATE
+         SET last_ts = EXCLUDED.last_ts,
+             breach_started_ts = EXCLUDED.breach_started_ts,
+             breach_started_value = EXCLUDED.breach_started_value"
+    )
+        .bind(state_rule_ids)
+        .bind(state_inventory_numbers)
+        .bind(last_timestamps)
+        .bind(breach_timestamps)
+        .bind(breach_values)
+        .execute(&mut *conn)
+        .await?;
+
+    for (rule_id, inventory_number, transition) in transitions {
+        let (event, alert) = match transition {
+            AlertTransition::Opened { ts, value } => {
+                let alert = query_as::<_, Alert>(
+                    "WITH a AS (
+                         INSERT INTO alerts (rule_id, sensor_inventory_number, opened_ts, opened_value)
+                         VALUES ($1, $2, $3, $4)
+                         RETURNING *
+                     )
+                     SELECT a.id, a.rule_id, ms.station_id, a.sensor_inventory_number,
+                            a.opened_ts, a.opened_value, a.closed_ts, a.closed_value
+                     FROM a JOIN meteostations_sensors ms ON ms.inventory_number = a.sensor_inventory_number"
+                )
+                    .bind(rule_id)
+                    .bind(inventory_number)
+                    .bind(ts)
+                    .bind(value)
+                    .fetch_one(&mut *conn)
+                    .await?;
+
+                (WebhookEvent::AlertOpened, alert)
+            }
+            AlertTransition::Closed { ts, value } => {
+                let alert = query_as::<_, Alert>(
+                    "WITH a AS (
+                         UPDATE alerts SET closed_ts = $3, closed_value = $4
+                         WHERE rule_id = $1 AND sensor_inventory_number = $2 AND closed_ts IS NULL
+                         RETURNING *
+                     )
+                     SELECT a.id, a.rule_id, ms.station_id, a.sensor_inventory_number,
+                            a.opened_ts, a.opened_value, a.closed_ts, a.closed_value
+                     FROM a JOIN meteostations_sensors ms ON ms.inventory_number = a.sensor_inventory_number"
+                )
+                    .bind(rule_id)
+                    .bind(inventory_number)
+                    .bind(ts)
+                    .bind(value)
+                    .fetch_one(&mut *conn)
+                    .await?;
+
+                (WebhookEvent::AlertClosed, alert)
+            }
+        };
+
+        enqueue_webhook_event(&mut *conn, event, || alert).await?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use sqlx::{PgPool, query};
+use crate::auth::{generate_api_key, hash_api_key, Scope};
+use crate::error::ApiError;
+use crate::models::{ApiKey, ApiKeyCreated, ApiKeyRequest};
+
+pub async fn fetch_api_keys(pool: &PgPool) -> Result<Vec<ApiKey>, ApiError> {
+    let rows = query!("SELECT id, name, scopes, station_id, created_at, revoked_at FROM api_keys ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| ApiKey {
+            id: row.id,
+            name: row.name,
+            scopes: row.scopes.iter().filter_map(|scope| scope.parse().ok()).collect(),
+            station_id: row.station_id,
+            created_at: row.created_at,
+            revoked_at: row.revoked_at,
+        })
+        .collect())
+}
+
+pub async fn insert_api_key(pool: &PgPool, item: &ApiKeyRequest) -> Result<ApiKeyCreated, ApiError> {
+    if item.scopes.is_empty() {
+        return Err(ApiError::Validation(String::from("an API key needs at least one scope")));
+    }
+
+    if item.station_id.is_some() && item.scopes.contains(&Scope::Admin) {
+        return Err(ApiError::Validation(String::from("a station-bound API key cannot have the admin scope")));
+    }
+
+    let key = generate_api_key();
+    let scopes: Vec<&str> = item.scopes.iter().map(Scope::as_str).collect();
+
+    let id = query!(
+        "INSERT INTO api_keys (name, key_hash, scopes, station_id) VALUES ($1, $2, $3, $4) RETURNING id",
+        item.name,
+        hash_api_key(&key),
+        &scopes as &[&str],
+        item.station_id
+    )
+        .fetch_one(pool)
+        .await?
+        .id;
+
+    Ok(ApiKeyCreated {
+        id,
+        name: item.name.clone(),
+        scopes: item.scopes.clone(),
+        station_id: item.station_id,
+        key,
+    })
+}
+
+pub async fn revoke_api_key(pool: &PgPool, key_id: i32) -> Result<(), ApiError> {
+    let result = query!(
+        "UPDATE api_keys SET revoked_at = now() WHERE id = $1 AND revoked_at IS NULL",
+        key_id
+    )
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("active API key {} not found", key_id)));
+    }
+
+    Ok(())
+}
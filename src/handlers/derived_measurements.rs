@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::NaiveDateTime;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder};
+use crate::derived::{Conditions, DerivedVariable, Quantity};
+use crate::error::ApiError;
+use crate::handlers::measurements::{MeasurementCursor, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+use crate::models::{
+    DerivedMeasurement, DerivedMeasurementQuery, DerivedType, Measurement, MeasurementPage, MeasurementQuery, SortOrder,
+};
+use crate::qc::QcFlag;
+use crate::units::{Conversion, Unit, UnitSelection};
+
+pub fn derived_types() -> Vec<DerivedType> {
+    DerivedVariable::ALL
+        .iter()
+        .map(|variable| DerivedType {
+            id: variable.type_id(),
+            name: *variable,
+            units: variable.units().to_string(),
+            inputs: variable.inputs().to_vec(),
+        })
+        .collect()
+}
+
+#[derive(FromRow)]
+struct QuantityRow {
+    station_id: i32,
+    ts: NaiveDateTime,
+    quantity: String,
+    units: String,
+    value: Option<BigDecimal>,
+    count: i64,
+}
+
+/// Readings of one station at one timestamp, as a sum and count per quantity.
+type Slot = (i32, NaiveDateTime, HashMap<Quantity, (f64, f64)>);
+
+/// Station timestamps to derive variables for, in `(ts, station_id)` order.
+struct SlotRange {
+    meteostation: Option<i32>,
+    from: Option<NaiveDateTime>,
+    to: Option<NaiveDateTime>,
+    order: SortOrder,
+    /// Continue after this `(ts, station_id)`.
+    after: Option<(NaiveDateTime, i32)>,
+    limit: i64,
+}
+
+fn derived_variable(type_id: i32) -> Result<DerivedVariable, ApiError> {
+    DerivedVariable::from_type_id(type_id).ok_or_else(|| ApiError::Validation(format!("unknown derived type {}", type_id)))
+}
+
+/// Computes derived variables from the readings each station took at the same timestamp,
+/// using corrected, else calibrated, values converted to the units the formulas expect, and
+/// skipping readings flagged `bad`. Several sensors or types of one quantity at a station
+/// are averaged. `limit` caps the number of station timestamps read, oldest first.
+pub async fn fetch_derived_measurements(
+    pool: &PgPool,
+    query: &DerivedMeasurementQuery,
+) -> Result<Vec<DerivedMeasurement>, ApiError> {
+    let variables: Vec<DerivedVariable> = match query.r#type {
+        Some(type_id) => vec![derived_variable(type_id)?],
+        None => DerivedVariable::ALL.to_vec(),
+    };
+    let range = SlotRange {
+        meteostation: query.meteostation,
+        from: query.from,
+        to: query.to,
+        order: SortOrder::Asc,
+        after: None,
+        limit: query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT),
+    };
+
+    let (derived, _) = compute_derived(pool, &variables, &range).await?;
+    Ok(derived)
+}
+
+/// A page of one derived type for `GET /api/measurements?type=-N`. Pages hold `limit` station
+/// timestamps, so they may carry fewer readings where the inputs were missing. The cursor keeps
+/// the station ID in place of the inventory number.
+pub async fn fetch_derived_page(
+    pool: &PgPool,
+    query: &MeasurementQuery,
+    cursor: Option<&MeasurementCursor>,
+) -> Result<MeasurementPage, ApiError> {
+    let type_id = query.r#type.unwrap_or_default();
+    let variable = derived_variable(type_id)?;
+
+    if query.sensor.is_some() || query.inventory_number.is_some() || query.qc.is_some() {
+        return Err(ApiError::Validation(String::from(
+            "derived types are computed per station, so sensor, inventory_number and qc do not apply",
+        )));
+    }
+    let after = match cursor {
+        Some(cursor) => match cursor.sensor_inventory_number.parse() {
+            Ok(station_id) if cursor.r#type == Some(type_id) => Some((cursor.ts, station_id)),
+            _ => return Err(ApiError::Validation(String::from("invalid cursor"))),
+        },
+        None => None,
+    };
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    let range = SlotRange {
+        meteostation: query.meteostation,
+        from: query.from,
+        to: query.to,
+        order: query.order.unwrap_or_default(),
+        after,
+        limit: limit + 1,
+    };
+    let (mut derived, slots) = compute_derived(pool, &[variable], &range).await?;
+
+    let mut next_cursor = None;
+    if let Some(&(ts, station_id)) = slots.get(limit as usize) {
+        derived.retain(|d| (d.ts, d.station_id) != (ts, station_id));
+        let (ts, station_id) = slots[limit as usize - 1];
+        next_cursor = Some(
+            MeasurementCursor { ts, sensor_inventory_number: station_id.to_string(), r#type: Some(type_id) }.encode(),
+        );
+    }
+
+    let selection = query.units.as_deref().map(UnitSelection::parse).transpose()?;
+    let conversion = match &selection {
+        Some(selection) => selection.conversion(type_id, variable.units())?,
+        None => None,
+    };
+    let units = selection.map(|_| match &conversion {
+        Some(conversion) => conversion.target().to_string(),
+        None => variable.units().to_string(),
+    });
+
+    let measurements = derived
+        .into_iter()
+        .map(|d| Measurement {
+            sensor_inventory_number: String::new(),
+            station_id: Some(d.station_id),
+            calibrated_value: Some(match &conversion {
+                Some(conversion) => conversion.apply(&d.value),
+                None => d.value.clone(),
+            }),
+            value: d.value,
+            units: units.clone(),
+            ts: d.ts,
+            r#type: Some(d.r#type),
+            qc_flag: QcFlag::Good,
+            qc_checks: Vec::new(),
+            corrected_value: None,
+            qc_note: None,
+            reviewed_by: None,
+            reviewed_at: None,
+        })
+        .collect();
+
+    Ok(MeasurementPage { measurements, next_cursor, unconverted_types: Vec::new() })
+}
+
+/// Derived readings of `variables` over `range`, with the station timestamps read in order.
+async fn compute_derived(
+    pool: &PgPool,
+    variables: &[DerivedVariable],
+    range: &SlotRange,
+) -> Result<(Vec<DerivedMeasurement>, Vec<(NaiveDateTime, i32)>), ApiError> {
+
+    let mut quantities: Vec<&str> = variables
+        .iter()
+        .flat_map(|variable| variable.inputs())
+        .map(Quantity::as_str)
+        .collect();
+    quantities.sort_unstable();
+    quantities.dedup();
+
+    let (direction, comparison) = match range.order {
+        SortOrder::Asc => (" ASC", " > "),
+        SortOrder::Desc => (" DESC", " < "),
+    };
+
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        r#"
+        WITH readings AS (
+            SELECT ms.station_id, m.ts, mt.quantity, mt.units,
+                   COALESCE(m.corrected_value, m.calibrated_value, m.value) AS value
+            FROM measurements m
+            JOIN meteostations_sensors ms ON ms.inventory_number = m.sensor_inventory_number
+            JOIN measurements_type mt ON mt.id = m.type
+            WHERE m.qc_flag <> 'bad' AND mt.quantity = ANY("#,
+    );
+    builder.push_bind(quantities).push(")");
+
+    if let Some(station_id) = range.meteostation {
+        builder.push(" AND ms.station_id = ").push_bind(station_id);
+    }
+    if let Some(from) = range.from {
+        builder.push(" AND m.ts >= ").push_bind(from);
+    }
+    if let Some(to) = range.to {
+        builder.push(" AND m.ts < ").push_bind(to);
+    }
+    if let Some((ts, station_id)) = range.after {
+        builder
+            .push(" AND (m.ts, ms.station_id)")
+            .push(comparison)
+            .push("(")
+            .push_bind(ts)
+            .push(", ")
+            .push_bind(station_id)
+            .push(")");
+    }
+
+    builder
+        .push(
+            r#"
+        ), slots AS (
+            SELECT DISTINCT station_id, ts FROM readings"#,
+        )
+        .push(format!(" ORDER BY ts{0}, station_id{0} LIMIT ", direction))
+        .push_bind(range.limit)
+        .push(
+            r#"
+        )
+        SELECT r.station_id, r.ts, r.quantity, r.units, AVG(r.value) AS value, COUNT(*) AS count
+        FROM readings r
+        JOIN slots s ON s.station_id = r.station_id AND s.ts = r.ts
+        GROUP BY r.station_id, r.ts, r.quantity, r.units"#,
+        )
+        .push(format!(" ORDER BY r.ts{0}, r.station_id{0}", direction));
+
+    let rows = builder
+        .build_query_as::<QuantityRow>()
+        .fetch_all(pool)
+        .await?;
+
+    let mut conversions: HashMap<(String, Quantity), Option<Conversion>> = HashMap::new();
+    let mut derived = Vec::new();
+    let mut slots = Vec::new();
+    let mut slot: Option<Slot> = None;
+
+    for row in rows {
+        if slot.as_ref().is_some_and(|(station_id, ts, _)| (*station_id, *ts) != (row.station_id, row.ts)) {
+            push_derived(&mut derived, variables, slot.take());
+        }
+        let (_, _, readings) = slot.get_or_insert_with(|| {
+            slots.push((row.ts, row.station_id));
+            (row.station_id, row.ts, HashMap::new())
+        });
+
+        let (Ok(quantity), Some(value)) = (row.quantity.parse::<Quantity>(), row.value) else {
+            continue;
+        };
+        // Units outside the registry are taken to be the expected ones.
+        let conversion = conversions.entry((row.units, quantity)).or_insert_with_key(|(units, quantity)| {
+            units.parse::<Unit>().ok().and_then(|units| Conversion::between(units, quantity.unit()))
+        });
+        let value = match conversion {
+            Some(conversion) => conversion.apply(&value),
+            None => value,
+        };
+
+        // Types in different units are averaged once converted, weighted by their readings.
+        if let Some(value) = value.to_f64() {
+            let (sum, count) = readings.entry(quantity).or_default();
+            *sum += value * row.count as f64;
+            *count += row.count as f64;
+        }
+    }
+    push_derived(&mut derived, variables, slot);
+
+    Ok((derived, slots))
+}
+
+fn push_derived(
+    derived: &mut Vec<DerivedMeasurement>,
+    variables: &[DerivedVariable],
+    slot: Option<Slot>,
+) {
+    if let Some((station_id, ts, readings)) = slot {
+        let mut conditions = Conditions::default();
+        for (quantity, (sum, count)) in readings {
+            conditions.set(quantity, sum / count);
+        }
+
+        for variable in variables {
+            if let Some(value) = variable.compute_decimal(&conditions) {
+                derived.push(DerivedMeasurement { station_id, ts, r#type: variable.type_id(), value });
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use actix_web::web::Bytes;
+use arrow_array::{ArrayRef, Decimal128Array, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use bigdecimal::{BigDecimal, ToPrimitive};
+use chrono::SecondsFormat;
+use futures_util::stream::{self, Stream};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use sqlx::{FromRow, PgPool, QueryBuilder, query};
+use crate::csv::{write_record, CSV_CONTENT_TYPE};
+use crate::error::ApiError;
+use crate::handlers::measurements::{
+    load_conversions, push_keyset_page, push_measurement_filters, report_series, MeasurementCursor, MeasurementRow,
+};
+use crate::models::{ExportFormat, Measurement, MeasurementQuery};
+use crate::units::Conversion;
+
+/// Readings fetched per round trip while exporting.
+pub const EXPORT_CHUNK_SIZE: i64 = 1000;
+/// Readings per Parquet row group; a row group is buffered until it is complete.
+pub const PARQUET_ROW_GROUP_SIZE: usize = 64 * 1024;
+/// Decimal places of the `value` column in Arrow and Parquet exports.
+pub const VALUE_SCALE: i8 = 6;
+const VALUE_PRECISION: u8 = 38;
+
+pub const CSV_HEADER: [&str; 9] = [
+    "station_id",
+    "station_name",
+    "sensor_name",
+    "sensor_inventory_number",
+    "type_id",
+    "type_name",
+    "units",
+    "ts",
+    "value",
+];
+
+const EXPORT_SELECT: &str = r#"
+    SELECT ms.station_id, st.name AS station_name, s.name AS sensor_name, mt.name AS type_name, mt.units AS type_units,
+           m.sensor_inventory_number, m.value, m.calibrated_value, m.ts, m.type,
+           m.qc_flag, m.qc_checks,
+           m.corrected_value, m.qc_note, m.reviewed_by, m.reviewed_at, sm.measurment_formula AS formula
+    FROM measurements m
+    JOIN meteostations_sensors ms ON ms.inventory_number = m.sensor_inventory_number
+    JOIN meteostations st ON st.id = ms.station_id
+    JOIN sensors s ON s.id = ms.sensor_id
+    LEFT JOIN measurements_type mt ON mt.id = m.type
+    LEFT JOIN sensors_measurements sm ON sm.sensor_id = ms.sensor_id AND sm.type_id = m.type
+"#;
+
+#[derive(FromRow)]
+struct ExportRow {
+    station_id: i32,
+    station_name: String,
+    sensor_name: String,
+    type_name: Option<String>,
+    type_units: Option<String>,
+    #[sqlx(flatten)]
+    reading: MeasurementRow,
+}
+
+/// One exported reading: the calibrated value, or the corrected one with `series=corrected`,
+/// in the requested units.
+pub struct ExportRecord {
+    pub station_id: i32,
+    pub station_name: String,
+    pub sensor_name: String,
+    pub type_name: Option<String>,
+    pub units: Option<String>,
+    pub measurement: Measurement,
+}
+
+impl ExportRecord {
+    pub fn write_csv(&self, out: &mut String) {
+        let measurement = &self.measurement;
+        let value = measurement.calibrated_value.as_ref().unwrap_or(&measurement.value);
+
+        write_record(
+            out,
+            &[
+                self.station_id.to_string(),
+                self.station_name.clone(),
+                self.sensor_name.clone(),
+                measurement.sensor_inventory_number.clone(),
+                measurement.r#type.map(|type_id| type_id.to_string()).unwrap_or_default(),
+                self.type_name.clone().unwrap_or_default(),
+                self.units.clone().unwrap_or_default(),
+                measurement.ts.and_utc().to_rfc3339_opts(SecondsFormat::AutoSi, true),
+                value.to_string(),
+            ],
+        );
+    }
+}
+
+impl ExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => CSV_CONTENT_TYPE,
+            ExportFormat::Arrow => "application/vnd.apache.arrow.stream",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "measurements.csv",
+            ExportFormat::Arrow => "measurements.arrows",
+            ExportFormat::Parquet => "measurements.parquet",
+        }
+    }
+}
+
+/// Columns of Arrow and Parquet exports, matching `CSV_HEADER`.
+pub fn export_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("station_id", DataType::Int32, false),
+        Field::new("station_name", DataType::Utf8, false),
+        Field::new("sensor_name", DataType::Utf8, false),
+        Field::new("sensor_inventory_number", DataType::Utf8, false),
+        Field::new("type_id", DataType::Int32, true),
+        Field::new("type_name", DataType::Utf8, true),
+        Field::new("units", DataType::Utf8, true),
+        Field::new("ts", DataType::Timestamp(TimeUnit::Microsecond, Some(Arc::from("UTC"))), false),
+        Field::new("value", DataType::Decimal128(VALUE_PRECISION, VALUE_SCALE), true),
+    ])
+}
+
+/// `value` as a `Decimal128` with `VALUE_SCALE` places, or `None` when it does not fit.
+pub fn decimal_value(value: &BigDecimal) -> Option<i128> {
+    let (digits, _) = value.round(VALUE_SCALE.into()).with_scale(VALUE_SCALE.into()).as_bigint_and_exponent();
+    digits.to_i128().filter(|digits| digits.unsigned_abs() < 10u128.pow(VALUE_PRECISION.into()))
+}
+
+/// Readings as a record batch of `schema`.
+pub fn record_batch(schema: Arc<Schema>, records: &[ExportRecord]) -> Result<RecordBatch, ApiError> {
+    let values: Vec<Option<i128>> = records
+        .iter()
+        .map(|record| {
+            let measurement = &record.measurement;
+            decimal_value(measurement.calibrated_value.as_ref().unwrap_or(&measurement.value))
+        })
+        .collect();
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from_iter_values(records.iter().map(|record| record.station_id))),
+        Arc::new(StringArray::from_iter_values(records.iter().map(|record| &record.station_name))),
+        Arc::new(StringArray::from_iter_values(records.iter().map(|record| &record.sensor_name))),
+        Arc::new(StringArray::from_iter_values(
+            records.iter().map(|record| &record.measurement.sensor_inventory_number),
+        )),
+        Arc::new(Int32Array::from_iter(records.iter().map(|record| record.measurement.r#type))),
+        Arc::new(StringArray::from_iter(records.iter().map(|record| record.type_name.as_deref()))),
+        Arc::new(StringArray::from_iter(records.iter().map(|record| record.units.as_deref()))),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(
+                records.iter().map(|record| record.measurement.ts.and_utc().timestamp_micros()),
+            )
+            .with_timezone("UTC"),
+        ),
+        Arc::new(
+            Decimal128Array::from(values)
+                .with_precision_and_scale(VALUE_PRECISION, VALUE_SCALE)
+                .map_err(|err| ApiError::Internal(err.to_string()))?,
+        ),
+    ];
+
+    RecordBatch::try_new(schema, columns).map_err(|err| ApiError::Internal(err.to_string()))
+}
+
+/// Encodes chunks of readings into one export file; each call returns the bytes written since
+/// the previous one.
+pub enum ExportEncoder {
+    Csv { header: bool },
+    Arrow { schema: Arc<Schema>, writer: StreamWriter<Vec<u8>> },
+    Parquet { schema: Arc<Schema>, writer: ArrowWriter<Vec<u8>> },
+}
+
+impl ExportEncoder {
+    pub fn new(format: ExportFormat) -> Result<ExportEncoder, ApiError> {
+        let schema = Arc::new(export_schema());
+
+        match format {
+            ExportFormat::Csv => Ok(ExportEncoder::Csv { header: true }),
+            ExportFormat::Arrow => {
+                let writer = StreamWriter::try_new(Vec::new(), &schema).map_err(|err| ApiError::Internal(err.to_string()))?;
+                Ok(ExportEncoder::Arrow { schema, writer })
+            }
+            ExportFormat::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .set_max_row_group_size(PARQUET_ROW_GROUP_SIZE)
+                    .build();
+                let writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(properties))
+                    .map_err(|err| ApiError::Internal(err.to_string()))?;
+                Ok(ExportEncoder::Parquet { schema, writer })
+            }
+        }
+    }
+
+    pub fn encode(&mut self, records: &[ExportRecord]) -> Result<Vec<u8>, ApiError> {
+        match self {
+            ExportEncoder::Csv { header } => {
+                let mut out = String::new();
+                if std::mem::take(header) {
+                    write_record(&mut out, &CSV_HEADER);
+                }
+                for record in records {
+                    record.write_csv(&mut out);
+                }
+                Ok(out.into_bytes())
+            }
+            ExportEncoder::Arrow { schema, writer } => {
+                if !records.is_empty() {
+                    let batch = record_batch(schema.clone(), records)?;
+                    writer.write(&batch).map_err(|err| ApiError::Internal(err.to_string()))?;
+                }
+                Ok(std::mem::take(writer.get_mut()))
+            }
+            ExportEncoder::Parquet { schema, writer } => {
+                if !records.is_empty() {
+                    let batch = record_batch(schema.clone(), records)?;
+                    writer.write(&batch).map_err(|err| ApiError::Internal(err.to_string()))?;
+                }
+                // Completed row groups are written through; the writer tracks offsets itself.
+                Ok(std::mem::take(writer.inner_mut()))
+            }
+        }
+    }
+
+    /// Remaining bytes: the Arrow end-of-stream marker or the Parquet footer.
+    pub fn finish(self) -> Result<Vec<u8>, ApiError> {
+        match self {
+            ExportEncoder::Csv { .. } => Ok(Vec::new()),
+            ExportEncoder::Arrow { writer, .. } => writer.into_inner().map_err(|err| ApiError::Internal(err.to_string())),
+            ExportEncoder::Parquet { writer, .. } => writer.into_inner().map_err(|err| ApiError::Internal(err.to_string())),
+        }
+    }
+}
+
+struct ExportState {
+    pool: PgPool,
+    query: MeasurementQuery,
+    conversions: HashMap<i32, Conversion>,
+    cursor: Option<MeasurementCursor>,
+    /// Taken once the file is finished or failed, ending the stream.
+    encoder: Option<ExportEncoder>,
+    done: bool,
+}
+
+impl ExportState {
+    /// Next chunk of readings after `cursor`, advancing it.
+    async fn next_chunk(&mut self) -> Result<Vec<ExportRecord>, ApiError> {
+        let rows = {
+            let mut builder = QueryBuilder::new(EXPORT_SELECT);
+            push_measurement_filters(&mut builder, &self.query);
+            push_keyset_page(&mut builder, self.query.order.unwrap_or_default(), self.cursor.as_ref(), EXPORT_CHUNK_SIZE);
+
+            builder
+                .build_query_as::<ExportRow>()
+                .fetch_all(&self.pool)
+                .await?
+        };
+
+        self.done = (rows.len() as i64) < EXPORT_CHUNK_SIZE;
+        if let Some(last) = rows.last() {
+            self.cursor = Some(last.reading.cursor());
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let mut measurement = Measurement::from(row.reading);
+                report_series(&mut measurement, self.query.series, &self.conversions);
+                let conversion = measurement.r#type.and_then(|type_id| self.conversions.get(&type_id));
+
+                ExportRecord {
+                    station_id: row.station_id,
+                    station_name: row.station_name,
+                    sensor_name: row.sensor_name,
+                    type_name: row.type_name,
+                    units: conversion.map(|conversion| conversion.target().to_string()).or(row.type_units),
+                    measurement,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Rejects paging parameters, since an export holds every matching reading, and derived types,
+/// which are computed rather than stored.
+pub fn check_export_query(query: &MeasurementQuery) -> Result<(), ApiError> {
+    if query.limit.is_some() || query.cursor.is_some() {
+        return Err(ApiError::Validation(String::from("exports are not paged, limit and cursor are not supported")));
+    }
+    if query.r#type.is_some_and(|type_id| type_id < 0) {
+        return Err(ApiError::Validation(String::from("derived types cannot be exported")));
+    }
+
+    Ok(())
+}
+
+/// Every reading matching `query` as a CSV, Arrow IPC stream or Parquet file, fetched in keyset
+/// chunks of `EXPORT_CHUNK_SIZE` so the export is never held in memory.
+pub async fn export_measurements_stream(
+    pool: PgPool,
+    query: MeasurementQuery,
+    format: ExportFormat,
+) -> Result<impl Stream<Item = Result<Bytes, ApiError>>, ApiError> {
+    check_export_query(&query)?;
+
+    // Resolve the units up front, so an invalid selection fails the request instead of the stream.
+    let type_ids = match (&query.units, query.r#type) {
+        (None, _) => Vec::new(),
+        (Some(_), Some(type_id)) => vec![type_id],
+        (Some(_), None) => query!("SELECT id FROM measurements_type")
+            .fetch_all(&pool)
+            .await?
+            .into_iter()
+            .map(|row| row.id)
+            .collect(),
+    };
+    let conversions = load_conversions(&pool, query.units.as_deref(), type_ids.into_iter()).await?;
+
+    let state = ExportState {
+        pool,
+        query,
+        conversions,
+        cursor: None,
+        encoder: Some(ExportEncoder::new(format)?),
+        done: false,
+    };
+
+    Ok(stream::unfold(state, |mut state| async move {
+        let mut encoder = state.encoder.take()?;
+
+        if state.done {
+            return Some((encoder.finish().map(Bytes::from), state));
+        }
+
+        let encoded = match state.next_chunk().await {
+            Ok(records) => encoder.encode(&records),
+            Err(err) => Err(err),
+        };
+        if encoded.is_ok() {
+            state.encoder = Some(encoder);
+        }
+
+        Some((encoded.map(Bytes::from), state))
+    }))
+}
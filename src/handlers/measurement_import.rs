@@ -0,0 +1,195 @@
+use std::str::FromStr;
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDateTime};
+use sqlx::PgPool;
+use crate::csv::parse_records;
+use crate::error::ApiError;
+use crate::events::MeasurementEvents;
+use crate::handlers::measurements::{check_measurements, insert_measurements};
+use crate::models::{
+    Measurement, MeasurementImportError, MeasurementImportQuery, MeasurementImportResponse, MeasurementRequest,
+};
+use crate::qc::QcFlag;
+
+/// Field delimiter named by `?delimiter=`.
+pub fn parse_delimiter(delimiter: Option<&str>) -> Result<char, ApiError> {
+    let delimiter = match delimiter {
+        None => return Ok(','),
+        Some("tab" | "\\t") => return Ok('\t'),
+        Some(delimiter) => delimiter,
+    };
+
+    let mut chars = delimiter.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if c != '"' && c != '\r' && c != '\n' => Ok(c),
+        _ => Err(ApiError::Validation(format!("invalid delimiter {:?}, expected a single character", delimiter))),
+    }
+}
+
+/// Parses `value` with a chrono `strftime` format, or as RFC 3339 without one. Formats without
+/// an offset are read as UTC.
+pub fn parse_timestamp(value: &str, format: Option<&str>) -> Result<NaiveDateTime, String> {
+    let parsed = match format {
+        None => DateTime::parse_from_rfc3339(value).map(|ts| ts.naive_utc()),
+        Some(format) if format.contains("%z") || format.contains("%:z") || format.contains("%#z") => {
+            DateTime::parse_from_str(value, format).map(|ts| ts.naive_utc())
+        }
+        Some(format) => NaiveDateTime::parse_from_str(value, format),
+    };
+
+    parsed.map_err(|err| format!("invalid timestamp {}: {}", value, err))
+}
+
+/// Positions of the mapped columns in a record.
+struct ColumnMapping {
+    inventory_number: usize,
+    r#type: usize,
+    ts: usize,
+    value: usize,
+}
+
+impl ColumnMapping {
+    fn from_header(header: &[String], options: &MeasurementImportQuery) -> Result<ColumnMapping, ApiError> {
+        let position = |column: &Option<String>, default: &str| {
+            let name = column.as_deref().unwrap_or(default);
+            header
+                .iter()
+                .position(|field| field.trim() == name)
+                .ok_or_else(|| ApiError::Validation(format!("the header has no {} column", name)))
+        };
+
+        Ok(ColumnMapping {
+            inventory_number: position(&options.inventory_number_column, "sensor_inventory_number")?,
+            r#type: position(&options.type_column, "type_id")?,
+            ts: position(&options.ts_column, "ts")?,
+            value: position(&options.value_column, "value")?,
+        })
+    }
+
+    fn reading(&self, fields: &[String], timestamp_format: Option<&str>) -> Result<Measurement, String> {
+        let field = |index: usize| {
+            fields
+                .get(index)
+                .map(|field| field.trim())
+                .ok_or_else(|| format!("expected at least {} fields, found {}", index + 1, fields.len()))
+        };
+
+        let inventory_number = field(self.inventory_number)?;
+        if inventory_number.is_empty() {
+            return Err(String::from("missing sensor inventory number"));
+        }
+        let type_id = field(self.r#type)?;
+        let type_id = type_id
+            .parse::<i32>()
+            .map_err(|_| format!("invalid measurement type {}", type_id))?;
+        let ts = parse_timestamp(field(self.ts)?, timestamp_format)?;
+        let value = field(self.value)?;
+        let value = BigDecimal::from_str(value).map_err(|_| format!("invalid value {}", value))?;
+
+        Ok(Measurement {
+            sensor_inventory_number: inventory_number.to_string(),
+            station_id: None,
+            value,
+            calibrated_value: None,
+            units: None,
+            ts,
+            r#type: Some(type_id),
+            qc_flag: QcFlag::Good,
+            qc_checks: Vec::new(),
+            corrected_value: None,
+            qc_note: None,
+            reviewed_by: None,
+            reviewed_at: None,
+        })
+    }
+}
+
+/// Imports readings from a CSV document with a header line. Every record is validated like a
+/// `POST /api/measurements` reading and the valid ones are stored through `insert_measurements`
+/// in batches of `max_batch_size`, each committed on its own; invalid records and the lines of
+/// batches that could not be stored are reported by line and skipped. `accepted` counts the
+/// stored readings. A dry run stores nothing.
+pub async fn import_measurements_csv(
+    pool: &PgPool,
+    events: &MeasurementEvents,
+    body: &[u8],
+    options: &MeasurementImportQuery,
+    max_batch_size: usize,
+    station_id: Option<i32>,
+) -> Result<MeasurementImportResponse, ApiError> {
+    let delimiter = parse_delimiter(options.delimiter.as_deref())?;
+    let dry_run = options.dry_run.unwrap_or(false);
+    let text = std::str::from_utf8(body).map_err(|_| ApiError::Validation(String::from("the CSV file must be UTF-8")))?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut records = parse_records(text, delimiter).into_iter();
+    let header = match records.next() {
+        Some(record) => record.fields.map_err(|reason| ApiError::Validation(format!("invalid header: {}", reason)))?,
+        None => return Err(ApiError::Validation(String::from("the CSV file is empty"))),
+    };
+    let mapping = ColumnMapping::from_header(&header, options)?;
+
+    let mut rows = 0;
+    let mut errors = Vec::new();
+    let mut readings = Vec::new();
+    let mut lines = Vec::new();
+
+    for record in records {
+        rows += 1;
+        let reading = record
+            .fields
+            .and_then(|fields| mapping.reading(&fields, options.timestamp_format.as_deref()));
+
+        match reading {
+            Ok(reading) => {
+                readings.push(reading);
+                lines.push(record.line);
+            }
+            Err(reason) => errors.push(MeasurementImportError { line: record.line, reason }),
+        }
+    }
+
+    let batch_size = max_batch_size.max(1);
+    let mut accepted = 0;
+    let mut readings = readings.into_iter();
+    for batch_lines in lines.chunks(batch_size) {
+        let measurements: Vec<Measurement> = readings.by_ref().take(batch_size).collect();
+        let count = measurements.len();
+        let result = if dry_run {
+            check_measurements(pool, &measurements, station_id).await
+        } else {
+            let request = MeasurementRequest { measurements };
+            insert_measurements(pool, events, &request, batch_size, station_id)
+                .await
+                .map(|response| response.rejections)
+        };
+
+        // Earlier batches are already committed, so a failed batch is reported line by line
+        // rather than failing the import.
+        let rejections = match result {
+            Ok(rejections) => rejections,
+            Err(err) => {
+                log::error!("Failed to import lines {}-{}: {}", batch_lines[0], batch_lines[count - 1], err);
+                let reason = format!("batch not stored: {}", err.problem().detail);
+                errors.extend(batch_lines.iter().map(|&line| MeasurementImportError { line, reason: reason.clone() }));
+                continue;
+            }
+        };
+
+        accepted += count - rejections.len();
+        errors.extend(rejections.into_iter().map(|rejection| MeasurementImportError {
+            line: batch_lines[rejection.index],
+            reason: rejection.reason,
+        }));
+    }
+
+    errors.sort_by_key(|error| error.line);
+
+    Ok(MeasurementImportResponse {
+        dry_run,
+        rows,
+        accepted,
+        rejected: errors.len(),
+        errors,
+    })
+}
@@ -1,62 +1,124 @@
-use sqlx::{PgPool, query, query_as, Row};
+use bigdecimal::BigDecimal;
+use sqlx::{FromRow, PgPool, query, query_as};
+use crate::error::ApiError;
 use crate::models::{MeasurementType, MeasurementTypeRequest};
+use crate::units::Unit;
 
-pub async fn fetch_measurement_types(pool: &PgPool) -> Result<Vec<MeasurementType>, sqlx::Error> {
+#[derive(FromRow)]
+struct MeasurementTypeRow {
+    id: i32,
+    name: String,
+    units: String,
+    quantity: Option<String>,
+    qc_min: Option<BigDecimal>,
+    qc_max: Option<BigDecimal>,
+    qc_max_step: Option<BigDecimal>,
+    qc_flatline_count: Option<i32>,
+}
+
+impl From<MeasurementTypeRow> for MeasurementType {
+    fn from(row: MeasurementTypeRow) -> Self {
+        MeasurementType {
+            id: row.id,
+            name: row.name,
+            units: row.units,
+            quantity: row.quantity.and_then(|quantity| quantity.parse().ok()),
+            qc_min: row.qc_min,
+            qc_max: row.qc_max,
+            qc_max_step: row.qc_max_step,
+            qc_flatline_count: row.qc_flatline_count,
+        }
+    }
+}
+
+pub async fn fetch_measurement_types(pool: &PgPool) -> Result<Vec<MeasurementType>, ApiError> {
     let rows = query_as!(
-        MeasurementType,
-        "SELECT id, name, units FROM measurements_type"
+        MeasurementTypeRow,
+        "SELECT id, name, units, quantity, qc_min, qc_max, qc_max_step, qc_flatline_count FROM measurements_type"
     )
         .fetch_all(pool)
         .await?;
 
-    Ok(rows)
+    Ok(rows.into_iter().map(MeasurementType::from).collect())
 }
 
-pub async fn insert_measurement_type(pool: &PgPool, mtype: &MeasurementTypeRequest) -> Result<MeasurementType, sqlx::Error> {
-    let mtype_id = query(
-        "INSERT INTO measurements_type (name, units) VALUES ($1, $2) RETURNING id"
+/// Registered units are stored under their canonical symbol.
+pub async fn insert_measurement_type(pool: &PgPool, mtype: &MeasurementTypeRequest) -> Result<MeasurementType, ApiError> {
+    let units: Unit = mtype.units.parse()?;
+
+    let row = query_as!(
+        MeasurementTypeRow,
+        "INSERT INTO measurements_type (name, units, quantity, qc_min, qc_max, qc_max_step, qc_flatline_count)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING id, name, units, quantity, qc_min, qc_max, qc_max_step, qc_flatline_count",
+        mtype.name,
+        units.as_str(),
+        mtype.quantity.map(|quantity| quantity.as_str()),
+        mtype.qc_min,
+        mtype.qc_max,
+        mtype.qc_max_step,
+        mtype.qc_flatline_count
     )
-        .bind(&mtype.name)
-        .bind(&mtype.units)
         .fetch_one(pool)
-        .await?
-        .try_get(0)?;
+        .await?;
 
-    Ok(MeasurementType {
-        id: mtype_id,
-        name: mtype.name.clone(),
-        units: mtype.units.clone()
-    })
+    Ok(row.into())
 }
 
+/// Units of a type cannot change once readings are stored in them, as the readings would
+/// silently be reported in the new units.
 pub async fn update_one_measurement_type(
     pool: &PgPool,
     type_id: i32,
     item: &MeasurementTypeRequest,
-) -> Result<MeasurementType, sqlx::Error> {
-    let result = sqlx::query!(
+) -> Result<MeasurementType, ApiError> {
+    let units: Unit = item.units.parse()?;
+
+    let current = query!(
+        r#"SELECT units, EXISTS (SELECT 1 FROM measurements WHERE type = $1) AS "measured!" FROM measurements_type WHERE id = $1"#,
+        type_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("measurement type {} not found", type_id)))?;
+
+    if current.measured && current.units.parse::<Unit>().ok() != Some(units) {
+        return Err(ApiError::Conflict(format!(
+            "measurement type {} has measurements in {}, so its units cannot change to {}",
+            type_id, current.units, units
+        )));
+    }
+
+    let result = query_as!(
+        MeasurementTypeRow,
         r#"
         UPDATE measurements_type
         SET name = COALESCE($1, name),
-            units = COALESCE($2, units)
-        WHERE id = $3
-        RETURNING id, name, units
+            units = COALESCE($2, units),
+            quantity = $3,
+            qc_min = $4,
+            qc_max = $5,
+            qc_max_step = $6,
+            qc_flatline_count = $7
+        WHERE id = $8
+        RETURNING id, name, units, quantity, qc_min, qc_max, qc_max_step, qc_flatline_count
         "#,
         item.name,
-        item.units,
+        units.as_str(),
+        item.quantity.map(|quantity| quantity.as_str()),
+        item.qc_min,
+        item.qc_max,
+        item.qc_max_step,
+        item.qc_flatline_count,
         type_id
     )
         .fetch_one(pool)
         .await?;
 
-    Ok(MeasurementType {
-        id: result.id,
-        name: result.name,
-        units: result.units
-    })
+    Ok(result.into())
 }
 
-pub async fn delete_one_measurement_type(pool: &PgPool, type_id: i32) -> Result<(), sqlx::Error> {
+pub async fn delete_one_measurement_type(pool: &PgPool, type_id: i32) -> Result<(), ApiError> {
     let count = sqlx::query!(
         "SELECT COUNT(*) FROM sensors_measurements WHERE type_id = $1",
         type_id
@@ -66,15 +128,19 @@ pub async fn delete_one_measurement_type(pool: &PgPool, type_id: i32) -> Result<
         .count;
 
     if count > Some(0) {
-        return Err(sqlx::Error::RowNotFound);
+        return Err(ApiError::Conflict(String::from("measurement type is used by sensors_measurements")));
     }
 
-    query!(
+    let result = query!(
         "DELETE FROM measurements_type WHERE id = $1",
         type_id
     )
         .execute(pool)
         .await?;
 
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("measurement type {} not found", type_id)));
+    }
+
     Ok(())
 }
\ No newline at end of file
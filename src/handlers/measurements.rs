@@ -1,57 +1,1105 @@
-use actix_web::web;
-use sqlx::{PgPool, query, query_as};
-use crate::models::{Measurement, MeasurementRequest, MeasurementQuery};
+use std::collections::{HashMap, HashSet};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, NaiveDateTime};
+use serde::Serialize;
+use sqlx::{FromRow, PgConnection, PgPool, Postgres, QueryBuilder, query, query_as};
+use crate::error::ApiError;
+use crate::delivery::WebhookEvent;
+use crate::handlers::alerts::{evaluate_alerts, AlertReading};
+use crate::handlers::derived_measurements::fetch_derived_page;
+use crate::handlers::webhooks::enqueue_webhook_events;
+use crate::events::{MeasurementEvent, MeasurementEvents};
+use crate::formula::Formula;
+use crate::qc::{QcFlag, QcLimits};
+use crate::units::{Conversion, Unit, UnitSelection};
+use crate::models::{
+    Measurement, MeasurementAggregate, MeasurementIngestResponse, MeasurementPage, MeasurementQuery,
+    MeasurementRejection, MeasurementRequest, MeasurementReview, MeasurementReviewEntry,
+    MeasurementReviewQuery, MeasurementSeries, MeasurementStreamQuery, SortOrder,
+};
+use crate::auth::Identity;
 
-pub async fn fetch_all_measurements(pool: &PgPool) -> Result<Vec<Measurement>, sqlx::Error> {
+#[derive(FromRow)]
+pub struct MeasurementRow {
+    sensor_inventory_number: String,
+    value: BigDecimal,
+    calibrated_value: Option<BigDecimal>,
+    ts: NaiveDateTime,
+    r#type: Option<i32>,
+    qc_flag: String,
+    qc_checks: Vec<String>,
+    corrected_value: Option<BigDecimal>,
+    qc_note: Option<String>,
+    reviewed_by: Option<String>,
+    reviewed_at: Option<NaiveDateTime>,
+    formula: Option<String>,
+}
 
-    let measurements = query_as!(
-        Measurement,
-        "SELECT sensor_inventory_number, value, ts, type FROM measurements"
-    )
+impl MeasurementRow {
+    /// Keyset position right after this reading.
+    pub fn cursor(&self) -> MeasurementCursor {
+        MeasurementCursor {
+            ts: self.ts,
+            sensor_inventory_number: self.sensor_inventory_number.clone(),
+            r#type: self.r#type,
+        }
+    }
+}
+
+impl From<MeasurementRow> for Measurement {
+    fn from(row: MeasurementRow) -> Self {
+        // Readings stored before calibration existed are calibrated with the current formula.
+        let calibrated_value = row.calibrated_value.or_else(|| {
+            row.formula
+                .as_deref()
+                .and_then(|formula| Formula::parse(formula).ok())
+                .and_then(|formula| formula.apply(&row.value).ok())
+        });
+
+        Measurement {
+            sensor_inventory_number: row.sensor_inventory_number,
+            station_id: None,
+            value: row.value,
+            calibrated_value,
+            units: None,
+            ts: row.ts,
+            r#type: row.r#type,
+            qc_flag: row.qc_flag.parse().unwrap_or_default(),
+            qc_checks: row.qc_checks,
+            corrected_value: row.corrected_value,
+            qc_note: row.qc_note,
+            reviewed_by: row.reviewed_by,
+            reviewed_at: row.reviewed_at,
+        }
+    }
+}
+
+pub const DEFAULT_PAGE_LIMIT: i64 = 1000;
+pub const MAX_PAGE_LIMIT: i64 = 10000;
+
+/// Keyset position of the last reading on a page, in `(ts, sensor_inventory_number, type)` order.
+pub struct MeasurementCursor {
+    pub ts: NaiveDateTime,
+    pub sensor_inventory_number: String,
+    pub r#type: Option<i32>,
+}
+
+impl MeasurementCursor {
+    pub fn encode(&self) -> String {
+        let type_id = self.r#type.map(|t| t.to_string()).unwrap_or_default();
+
+        format!("{}|{}|{}", self.ts.and_utc().timestamp_micros(), type_id, self.sensor_inventory_number)
+            .bytes()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    pub fn decode(cursor: &str) -> Option<MeasurementCursor> {
+        if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+            return None;
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16).ok())
+            .collect::<Option<Vec<u8>>>()?;
+        let decoded = String::from_utf8(bytes).ok()?;
+        let mut parts = decoded.splitn(3, '|');
+        let micros = parts.next()?.parse().ok()?;
+        let r#type = match parts.next()? {
+            "" => None,
+            type_id => Some(type_id.parse().ok()?),
+        };
+
+        Some(MeasurementCursor {
+            ts: DateTime::from_timestamp_micros(micros)?.naive_utc(),
+            sensor_inventory_number: parts.next()?.to_string(),
+            r#type,
+        })
+    }
+}
+
+pub const MEASUREMENT_SELECT: &str = r#"
+    SELECT m.sensor_inventory_number, m.value, m.calibrated_value, m.ts, m.type,
+           m.qc_flag, m.qc_checks,
+           m.corrected_value, m.qc_note, m.reviewed_by, m.reviewed_at, sm.measurment_formula AS formula
+    FROM measurements m
+    LEFT JOIN meteostations_sensors ms ON ms.inventory_number = m.sensor_inventory_number
+    LEFT JOIN sensors_measurements sm ON sm.sensor_id = ms.sensor_id AND sm.type_id = m.type
+"#;
+
+/// Appends a `WHERE` clause with every filter set in `query`, binding each value as a parameter.
+/// Expects `measurements m` joined with `meteostations_sensors ms` in the pushed query.
+pub fn push_measurement_filters<'a>(builder: &mut QueryBuilder<'a, Postgres>, query: &'a MeasurementQuery) {
+    builder.push(" WHERE TRUE");
+
+    if let Some(station_id) = query.meteostation {
+        builder.push(" AND ms.station_id = ").push_bind(station_id);
+    }
+    if let Some(sensor_id) = query.sensor {
+        builder.push(" AND ms.sensor_id = ").push_bind(sensor_id);
+    }
+    if let Some(inventory_number) = &query.inventory_number {
+        builder.push(" AND m.sensor_inventory_number = ").push_bind(inventory_number);
+    }
+    if let Some(type_id) = query.r#type {
+        builder.push(" AND m.type = ").push_bind(type_id);
+    }
+    if let Some(from) = query.from {
+        builder.push(" AND m.ts >= ").push_bind(from);
+    }
+    if let Some(to) = query.to {
+        builder.push(" AND m.ts < ").push_bind(to);
+    }
+    if let Some(qc) = query.qc {
+        builder.push(" AND m.qc_flag = ").push_bind(qc.as_str());
+    }
+}
+
+/// Units of measurement types as reported with `?units=`.
+#[derive(Default)]
+pub struct TypeUnits {
+    /// Conversions of the types whose values change; types left as stored are missing.
+    pub conversions: HashMap<i32, Conversion>,
+    /// Units each type is reported in, after conversion.
+    pub reported: HashMap<i32, String>,
+    /// Types a unit system was requested for whose stored units are not in the registry,
+    /// so their values are left as stored.
+    pub unconverted: Vec<i32>,
+}
+
+/// Resolves `?units=` for the given measurement types; without it nothing is converted or reported.
+pub async fn load_type_units(
+    pool: &PgPool,
+    units: Option<&str>,
+    type_ids: impl Iterator<Item = i32>,
+) -> Result<TypeUnits, ApiError> {
+    let selection = match units {
+        Some(units) => UnitSelection::parse(units)?,
+        None => return Ok(TypeUnits::default()),
+    };
+    let type_ids: Vec<i32> = type_ids.collect::<HashSet<_>>().into_iter().collect();
+
+    let rows = query!("SELECT id, units FROM measurements_type WHERE id = ANY($1)", &type_ids)
+        .fetch_all(pool)
+        .await?;
+
+    let mut type_units = TypeUnits::default();
+    for row in rows {
+        let reported = match selection.conversion(row.id, &row.units)? {
+            Some(conversion) => {
+                let target = conversion.target().to_string();
+                type_units.conversions.insert(row.id, conversion);
+                target
+            }
+            None => {
+                if selection.system.is_some() && row.units.parse::<Unit>().is_err() {
+                    type_units.unconverted.push(row.id);
+                }
+                row.units
+            }
+        };
+        type_units.reported.insert(row.id, reported);
+    }
+    type_units.unconverted.sort_unstable();
+
+    Ok(type_units)
+}
+
+/// Conversions requested with `?units=` for the given measurement types; types left as stored
+/// are missing from the map.
+pub async fn load_conversions(
+    pool: &PgPool,
+    units: Option<&str>,
+    type_ids: impl Iterator<Item = i32>,
+) -> Result<HashMap<i32, Conversion>, ApiError> {
+    Ok(load_type_units(pool, units, type_ids).await?.conversions)
+}
+
+pub async fn fetch_measurements(
+    pool: &PgPool,
+    query: &MeasurementQuery,
+    cursor: Option<&MeasurementCursor>,
+) -> Result<MeasurementPage, ApiError> {
+    if query.r#type.is_some_and(|type_id| type_id < 0) {
+        return fetch_derived_page(pool, query, cursor).await;
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+
+    let mut builder = QueryBuilder::new(MEASUREMENT_SELECT);
+    push_measurement_filters(&mut builder, query);
+    push_keyset_page(&mut builder, query.order.unwrap_or_default(), cursor, limit + 1);
+
+    let mut rows = builder
+        .build_query_as::<MeasurementRow>()
         .fetch_all(pool)
         .await?;
 
-    Ok(measurements)
+    let has_more = rows.len() as i64 > limit;
+    rows.truncate(limit as usize);
+
+    let next_cursor = match rows.last() {
+        Some(last) if has_more => Some(last.cursor().encode()),
+        _ => None,
+    };
+
+    let mut measurements: Vec<Measurement> = rows.into_iter().map(Measurement::from).collect();
+    let type_units = load_type_units(pool, query.units.as_deref(), measurements.iter().filter_map(|m| m.r#type)).await?;
+    for measurement in &mut measurements {
+        report_series(measurement, query.series, &type_units.conversions);
+        measurement.units = measurement.r#type.and_then(|type_id| type_units.reported.get(&type_id).cloned());
+    }
+
+    Ok(MeasurementPage { measurements, next_cursor, unconverted_types: type_units.unconverted })
+}
+
+/// Continues a `(ts, sensor_inventory_number, type)` keyset after `cursor` in `order`, at most `limit` rows.
+pub fn push_keyset_page<'a>(
+    builder: &mut QueryBuilder<'a, Postgres>,
+    order: SortOrder,
+    cursor: Option<&'a MeasurementCursor>,
+    limit: i64,
+) {
+    let (direction, comparison) = match order {
+        SortOrder::Asc => (" ASC", " > "),
+        SortOrder::Desc => (" DESC", " < "),
+    };
+
+    if let Some(cursor) = cursor {
+        builder
+            .push(" AND (m.ts, m.sensor_inventory_number, COALESCE(m.type, 0))")
+            .push(comparison)
+            .push("(")
+            .push_bind(cursor.ts)
+            .push(", ")
+            .push_bind(&cursor.sensor_inventory_number)
+            .push(", ")
+            .push_bind(cursor.r#type.unwrap_or(0))
+            .push(")");
+    }
+
+    builder
+        .push(" ORDER BY m.ts").push(direction)
+        .push(", m.sensor_inventory_number").push(direction)
+        .push(", COALESCE(m.type, 0)").push(direction)
+        .push(" LIMIT ").push_bind(limit);
+}
+
+/// Reports the corrected value of `series=corrected` as the calibrated value, then converts
+/// both. The recorded value stays as the sensor sent it; uncalibrated readings are reported
+/// converted as their calibrated value.
+pub fn report_series(measurement: &mut Measurement, series: MeasurementSeries, conversions: &HashMap<i32, Conversion>) {
+    if series == MeasurementSeries::Corrected {
+        if let Some(corrected_value) = &measurement.corrected_value {
+            measurement.calibrated_value = Some(corrected_value.clone());
+        }
+    }
+
+    if let Some(conversion) = measurement.r#type.and_then(|type_id| conversions.get(&type_id)) {
+        let calibrated_value = measurement.calibrated_value.as_ref().unwrap_or(&measurement.value);
+        measurement.calibrated_value = Some(conversion.apply(calibrated_value));
+        measurement.corrected_value = measurement.corrected_value.as_ref().map(|value| conversion.apply(value));
+    }
+}
+
+#[derive(FromRow)]
+struct MeasurementEventRow {
+    id: i64,
+    station_id: i32,
+    sensor_id: i32,
+    #[sqlx(flatten)]
+    measurement: MeasurementRow,
+}
 
+impl From<MeasurementEventRow> for MeasurementEvent {
+    fn from(row: MeasurementEventRow) -> Self {
+        MeasurementEvent {
+            id: row.id,
+            station_id: row.station_id,
+            sensor_id: row.sensor_id,
+            measurement: row.measurement.into(),
+        }
+    }
 }
 
-pub async fn fetch_condition_measurements(pool: &PgPool, query: web::Query<MeasurementQuery>) -> Result<Vec<Measurement>, sqlx::Error> {
+/// Readings with their stream position as `id`.
+const MEASUREMENT_EVENT_SELECT: &str = r#"
+        SELECT s.seq AS id, ms.station_id, ms.sensor_id,
+               m.sensor_inventory_number, m.value, m.calibrated_value, m.ts, m.type,
+               m.qc_flag, m.qc_checks,
+           m.corrected_value, m.qc_note, m.reviewed_by, m.reviewed_at, sm.measurment_formula AS formula
+        FROM measurement_stream s
+        JOIN measurements m ON m.id = s.measurement_id
+        JOIN meteostations_sensors ms ON ms.inventory_number = m.sensor_inventory_number
+        LEFT JOIN sensors_measurements sm ON sm.sensor_id = ms.sensor_id AND sm.type_id = m.type"#;
+
+/// Stored readings with a stream position above `after_id`, oldest first, for replaying a live
+/// stream. Positions are handed out in commit order by `sequence_measurements`, so nothing below
+/// a streamed position can appear later. The flag is `false` when the page limit was hit and
+/// more readings follow.
+pub async fn fetch_measurement_events(
+    pool: &PgPool,
+    query: &MeasurementStreamQuery,
+    after_id: i64,
+) -> Result<(Vec<MeasurementEvent>, bool), ApiError> {
+    let mut builder = QueryBuilder::new(MEASUREMENT_EVENT_SELECT);
+    builder.push(" WHERE s.seq > ").push_bind(after_id);
 
-    let mut sql = String::from("SELECT * FROM measurements JOIN meteostations_sensors ON sensor_inventory_number = inventory_number WHERE 1 = 1");
     if let Some(station_id) = query.meteostation {
-        sql.push_str(&format!(" AND station_id = {}", station_id));
+        builder.push(" AND ms.station_id = ").push_bind(station_id);
     }
     if let Some(sensor_id) = query.sensor {
-        sql.push_str(&format!(" AND sensor_id = {}", sensor_id));
+        builder.push(" AND ms.sensor_id = ").push_bind(sensor_id);
     }
+    if let Some(type_id) = query.r#type {
+        builder.push(" AND m.type = ").push_bind(type_id);
+    }
+
+    builder.push(" ORDER BY s.seq LIMIT ").push_bind(MAX_PAGE_LIMIT);
 
-    let measurements = sqlx::query_as::<_, Measurement>(&sql)
+    let rows = builder
+        .build_query_as::<MeasurementEventRow>()
         .fetch_all(pool)
         .await?;
 
-    Ok(measurements)
+    let complete = (rows.len() as i64) < MAX_PAGE_LIMIT;
+    let events = rows.into_iter().map(MeasurementEvent::from).collect();
+
+    Ok((events, complete))
+}
+
+/// Readings given stream positions per `sequence_measurements` call.
+pub const SEQUENCE_BATCH_SIZE: i64 = 10_000;
+
+/// Advisory lock key letting one sequencer at a time hand out stream positions.
+const MEASUREMENT_STREAM_LOCK: i64 = 0x6d65_6173;
+
+/// Gives committed readings queued by `insert_measurements` their stream positions, oldest id
+/// first, and returns how many were sequenced along with, when `publish` is set, the sequenced
+/// readings as events. Sequencers run one at a time and commit before the next one reads the
+/// queue, so positions follow commit order; ingests themselves never wait for this.
+pub async fn sequence_measurements(pool: &PgPool, publish: bool) -> Result<(i64, Vec<MeasurementEvent>), ApiError> {
+    let mut tx = pool.begin().await?;
+
+    // Taken in its own statement so the queue is read with a snapshot that already sees every
+    // position the previous sequencer committed.
+    query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MEASUREMENT_STREAM_LOCK)
+        .execute(&mut *tx)
+        .await?;
+
+    let (count, first, last): (i64, Option<i64>, Option<i64>) = query_as(
+        "WITH moved AS (
+             DELETE FROM measurement_stream_pending
+             WHERE measurement_id IN (
+                 SELECT measurement_id FROM measurement_stream_pending ORDER BY measurement_id LIMIT $1
+             )
+             RETURNING measurement_id
+         ), sequenced AS (
+             INSERT INTO measurement_stream (measurement_id)
+             SELECT measurement_id FROM moved ORDER BY measurement_id
+             RETURNING seq
+         )
+         SELECT COUNT(*), MIN(seq), MAX(seq) FROM sequenced"
+    )
+        .bind(SEQUENCE_BATCH_SIZE)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let mut events = Vec::new();
+    if let (true, Some(first), Some(last)) = (publish, first, last) {
+        let mut builder = QueryBuilder::new(MEASUREMENT_EVENT_SELECT);
+        builder
+            .push(" WHERE s.seq BETWEEN ")
+            .push_bind(first)
+            .push(" AND ")
+            .push_bind(last)
+            .push(" ORDER BY s.seq");
+
+        events = builder
+            .build_query_as::<MeasurementEventRow>()
+            .fetch_all(&mut *tx)
+            .await?
+            .into_iter()
+            .map(MeasurementEvent::from)
+            .collect();
+    }
+
+    tx.commit().await?;
+
+    Ok((count, events))
 }
 
-pub async fn insert_measurements(pool: &PgPool, item: &MeasurementRequest) -> Result<(), sqlx::Error> {
-    for measurement in &item.measurements {
-       query!("INSERT INTO measurements (sensor_inventory_number, value, ts, type) VALUES ($1, $2, $3, $4)",
-           measurement.sensor_inventory_number,
-           measurement.value,
-           measurement.ts,
-           measurement.r#type
-       )
-           .execute(pool)
-           .await?;
+/// Most buckets one aggregate request may span.
+pub const MAX_AGGREGATE_BUCKETS: i64 = 10_000;
+
+/// Parses a bucket width such as `30s`, `15m`, `1h`, `1d` or `1w` into seconds.
+/// Buckets are aligned to Monday 2000-01-03, so weekly buckets start on Mondays.
+pub fn parse_interval(interval: &str) -> Option<i64> {
+    let interval = interval.trim();
+    let split = interval.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = interval.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+    let unit_seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    amount.checked_mul(unit_seconds).filter(|seconds| *seconds > 0)
+}
+
+/// Rejects aggregate requests over an open or too long range, or with paging parameters,
+/// since every bucket is returned at once.
+pub fn check_aggregate_range(query: &MeasurementQuery, interval_seconds: i64) -> Result<(), ApiError> {
+    if query.limit.is_some() || query.cursor.is_some() || query.order.is_some() {
+        return Err(ApiError::Validation(String::from("aggregates are not paged, limit, cursor and order are not supported")));
+    }
+
+    let (Some(from), Some(to)) = (query.from, query.to) else {
+        return Err(ApiError::Validation(String::from("aggregates require both from and to")));
+    };
+    if from >= to {
+        return Err(ApiError::Validation(String::from("from must be before to")));
+    }
+
+    let buckets = (to - from).num_seconds() / interval_seconds + 1;
+    if buckets > MAX_AGGREGATE_BUCKETS {
+        return Err(ApiError::Validation(format!(
+            "the range spans {} buckets of {}s, at most {} are allowed",
+            buckets, interval_seconds, MAX_AGGREGATE_BUCKETS
+        )));
     }
 
     Ok(())
 }
 
-pub async fn delete_measurement(pool: &PgPool, number: String) -> Result<(), sqlx::Error> {
+pub async fn fetch_measurement_aggregates(
+    pool: &PgPool,
+    query: &MeasurementQuery,
+    interval_seconds: i64,
+) -> Result<Vec<MeasurementAggregate>, ApiError> {
+    check_aggregate_range(query, interval_seconds)?;
+
+    let value = match query.series {
+        MeasurementSeries::Raw => "COALESCE(m.calibrated_value, m.value)",
+        MeasurementSeries::Corrected => "COALESCE(m.corrected_value, m.calibrated_value, m.value)",
+    };
+
+    let mut builder = QueryBuilder::new("SELECT date_bin(make_interval(secs => ");
+    builder
+        .push_bind(interval_seconds as f64)
+        .push(format!(
+            r#"), m.ts, TIMESTAMP '2000-01-03') AS bucket,
+               m.sensor_inventory_number, m.type,
+               COUNT(*) AS count,
+               MIN({value}) AS min,
+               MAX({value}) AS max,
+               AVG({value}) AS avg,
+               SUM({value}) AS sum,
+               (ARRAY_AGG({value} ORDER BY m.ts ASC))[1] AS first,
+               (ARRAY_AGG({value} ORDER BY m.ts DESC))[1] AS last
+            FROM measurements m
+            LEFT JOIN meteostations_sensors ms ON ms.inventory_number = m.sensor_inventory_number"#,
+        ));
+    push_measurement_filters(&mut builder, query);
+    builder.push(" GROUP BY bucket, m.sensor_inventory_number, m.type ORDER BY bucket, m.sensor_inventory_number, m.type");
+
+    let mut aggregates = builder
+        .build_query_as::<MeasurementAggregate>()
+        .fetch_all(pool)
+        .await?;
+
+    let type_units = load_type_units(pool, query.units.as_deref(), aggregates.iter().filter_map(|a| a.r#type)).await?;
+    for aggregate in &mut aggregates {
+        aggregate.units = aggregate.r#type.and_then(|type_id| type_units.reported.get(&type_id).cloned());
+        if let Some(conversion) = aggregate.r#type.and_then(|type_id| type_units.conversions.get(&type_id)) {
+            aggregate.min = conversion.apply(&aggregate.min);
+            aggregate.max = conversion.apply(&aggregate.max);
+            aggregate.avg = conversion.apply(&aggregate.avg);
+            aggregate.sum = conversion.apply_sum(&aggregate.sum, aggregate.count);
+            aggregate.first = conversion.apply(&aggregate.first);
+            aggregate.last = conversion.apply(&aggregate.last);
+        }
+    }
+
+    Ok(aggregates)
+}
+
+pub const INSERT_CHUNK_SIZE: usize = 5000;
+
+/// Readings per `measurement.created` webhook delivery.
+pub const WEBHOOK_BATCH_SIZE: usize = 1000;
+
+
+/// Period during which a sensor inventory number is installed at a station.
+pub struct SensorAssignment {
+    pub station_id: i32,
+    pub sensor_id: i32,
+    pub added_ts: Option<NaiveDateTime>,
+    pub removed_ts: Option<NaiveDateTime>,
+}
+
+impl SensorAssignment {
+    /// Whether a reading taken at `ts` falls within `added_ts`..`removed_ts`.
+    pub fn covers(&self, ts: NaiveDateTime) -> bool {
+        self.added_ts.is_none_or(|added_ts| added_ts <= ts)
+            && self.removed_ts.is_none_or(|removed_ts| ts < removed_ts)
+    }
+}
+
+/// Reference data needed to validate and calibrate a batch of readings, loaded once per batch.
+struct IngestContext {
+    assignments: HashMap<String, SensorAssignment>,
+    /// Measurement types declared in `sensors_measurements` per inventory number.
+    declared_types: HashSet<(String, i32)>,
+    formulas: HashMap<(String, i32), Formula>,
+    /// QC limits of the measurement types in the batch that have any set.
+    qc_limits: HashMap<i32, QcLimits>,
+    /// Station of the device token that sent the batch and the inventory numbers currently
+    /// assigned to it; readings for any other sensor are rejected.
+    station: Option<(i32, HashSet<String>)>,
+}
+
+impl IngestContext {
+    async fn load(
+        conn: &mut PgConnection,
+        measurements: &[Measurement],
+        station_id: Option<i32>,
+    ) -> Result<IngestContext, ApiError> {
+        let inventory_numbers: Vec<String> = measurements
+            .iter()
+            .map(|m| m.sensor_inventory_number.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        let assignments = query!(
+            "SELECT inventory_number, station_id, sensor_id, added_ts, removed_ts
+             FROM meteostations_sensors WHERE inventory_number = ANY($1)",
+            &inventory_numbers
+        )
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| {
+                let assignment = SensorAssignment {
+                    station_id: row.station_id,
+                    sensor_id: row.sensor_id,
+                    added_ts: row.added_ts,
+                    removed_ts: row.removed_ts,
+                };
+                (row.inventory_number, assignment)
+            })
+            .collect();
+
+        let rows = query!(
+            "SELECT ms.inventory_number, sm.type_id, sm.measurment_formula
+             FROM meteostations_sensors ms
+             JOIN sensors_measurements sm ON sm.sensor_id = ms.sensor_id
+             WHERE ms.inventory_number = ANY($1)",
+            &inventory_numbers
+        )
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut declared_types = HashSet::with_capacity(rows.len());
+        let mut formulas = HashMap::with_capacity(rows.len());
+        for row in rows {
+            if let Ok(formula) = Formula::parse(&row.measurment_formula) {
+                formulas.insert((row.inventory_number.clone(), row.type_id), formula);
+            }
+            declared_types.insert((row.inventory_number, row.type_id));
+        }
+
+        let type_ids: Vec<i32> = measurements
+            .iter()
+            .filter_map(|m| m.r#type)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+
+        let qc_limits = query!(
+            "SELECT id, qc_min, qc_max, qc_max_step, qc_flatline_count FROM measurements_type
+             WHERE id = ANY($1)
+               AND (qc_min IS NOT NULL OR qc_max IS NOT NULL
+                    OR qc_max_step IS NOT NULL OR qc_flatline_count IS NOT NULL)",
+            &type_ids
+        )
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|row| {
+                let limits = QcLimits {
+                    min: row.qc_min,
+                    max: row.qc_max,
+                    max_step: row.qc_max_step,
+                    flatline_count: row.qc_flatline_count,
+                };
+                (row.id, limits)
+            })
+            .collect();
+
+        let station = match station_id {
+            Some(station_id) => {
+                let assigned = query!(
+                    "SELECT inventory_number FROM meteostations_sensors
+                     WHERE station_id = $1 AND inventory_number = ANY($2)
+                       AND (added_ts IS NULL OR added_ts <= now())
+                       AND (removed_ts IS NULL OR removed_ts > now())",
+                    station_id,
+                    &inventory_numbers
+                )
+                    .fetch_all(&mut *conn)
+                    .await?
+                    .into_iter()
+                    .map(|row| row.inventory_number)
+                    .collect();
+
+                Some((station_id, assigned))
+            }
+            None => None,
+        };
+
+        Ok(IngestContext {
+            assignments,
+            declared_types,
+            formulas,
+            qc_limits,
+            station,
+        })
+    }
+
+    /// Returns the reason a reading cannot be stored, if any.
+    fn check(&self, measurement: &Measurement) -> Result<(), String> {
+        let inventory_number = &measurement.sensor_inventory_number;
+
+        let assignment = self
+            .assignments
+            .get(inventory_number)
+            .ok_or_else(|| format!("unknown sensor inventory number {}", inventory_number))?;
+
+        if let Some((station_id, assigned)) = &self.station {
+            if !assigned.contains(inventory_number) {
+                return Err(format!(
+                    "sensor inventory number {} is not currently assigned to station {}",
+                    inventory_number, station_id
+                ));
+            }
+        }
+
+        if !assignment.covers(measurement.ts) {
+            return Err(format!(
+                "reading at {} is outside the assignment of sensor inventory number {} to station {}",
+                measurement.ts, inventory_number, assignment.station_id
+            ));
+        }
+
+        let type_id = measurement
+            .r#type
+            .ok_or_else(|| String::from("reading has no measurement type"))?;
+
+        if !self.declared_types.contains(&(inventory_number.clone(), type_id)) {
+            return Err(format!(
+                "measurement type {} is not configured for sensor inventory number {}",
+                type_id, inventory_number
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn calibrate(&self, measurement: &Measurement) -> Option<BigDecimal> {
+        let key = (measurement.sensor_inventory_number.clone(), measurement.r#type?);
+        self.formulas.get(&key)?.apply(&measurement.value).ok()
+    }
+
+    /// Runs the QC checks of each accepted reading against the earlier readings of the same
+    /// sensor and type, both stored and in the batch. Returns the results in `accepted` order.
+    async fn quality_control(
+        &self,
+        conn: &mut PgConnection,
+        accepted: &[&Measurement],
+    ) -> Result<Vec<(QcFlag, Vec<&'static str>)>, ApiError> {
+        let mut results = vec![(QcFlag::Good, Vec::new()); accepted.len()];
+
+        let mut series: HashMap<(&str, i32), Vec<usize>> = HashMap::new();
+        for (index, measurement) in accepted.iter().enumerate() {
+            if let Some(type_id) = measurement.r#type.filter(|t| self.qc_limits.contains_key(t)) {
+                series.entry((&measurement.sensor_inventory_number, type_id)).or_default().push(index);
+            }
+        }
+        if series.is_empty() {
+            return Ok(results);
+        }
+
+        for indices in series.values_mut() {
+            indices.sort_by_key(|&index| accepted[index].ts);
+        }
+
+        let mut inventory_numbers = Vec::with_capacity(series.len());
+        let mut type_ids = Vec::with_capacity(series.len());
+        let mut first_ts = Vec::with_capacity(series.len());
+        let mut history_lens = Vec::with_capacity(series.len());
+        for ((inventory_number, type_id), indices) in &series {
+            inventory_numbers.push(inventory_number.to_string());
+            type_ids.push(*type_id);
+            first_ts.push(accepted[indices[0]].ts);
+            history_lens.push(self.qc_limits[type_id].history_len() as i64);
+        }
+
+        let rows = query!(
+            r#"
+            SELECT s.inventory_number AS "inventory_number!", s.type_id AS "type_id!", h.value AS "value!"
+            FROM UNNEST($1::varchar[], $2::integer[], $3::timestamp[], $4::bigint[])
+                AS s(inventory_number, type_id, first_ts, history_len)
+            CROSS JOIN LATERAL (
+                SELECT COALESCE(m.corrected_value, m.calibrated_value, m.value) AS value, m.ts
+                FROM measurements m
+                WHERE m.sensor_inventory_number = s.inventory_number
+                  AND m.type = s.type_id AND m.ts < s.first_ts
+                ORDER BY m.ts DESC
+                LIMIT s.history_len
+            ) h
+            ORDER BY h.ts
+            "#,
+            &inventory_numbers,
+            &type_ids,
+            &first_ts,
+            &history_lens
+        )
+            .fetch_all(&mut *conn)
+            .await?;
+
+        let mut history: HashMap<(String, i32), Vec<BigDecimal>> = HashMap::new();
+        for row in rows {
+            history.entry((row.inventory_number, row.type_id)).or_default().push(row.value);
+        }
+
+        for ((inventory_number, type_id), indices) in series {
+            let limits = &self.qc_limits[&type_id];
+            let mut values = history.remove(&(inventory_number.to_string(), type_id)).unwrap_or_default();
+
+            for index in indices {
+                let measurement = accepted[index];
+                let value = self.calibrate(measurement).unwrap_or_else(|| measurement.value.clone());
+                results[index] = limits.check(&values, &value);
+                values.push(value);
+            }
+        }
+
+        Ok(results)
+    }
+
+    fn alert_reading<'a>(&self, measurement: &'a Measurement) -> Option<AlertReading<'a>> {
+        let assignment = self.assignments.get(&measurement.sensor_inventory_number)?;
+
+        Some(AlertReading {
+            inventory_number: &measurement.sensor_inventory_number,
+            station_id: assignment.station_id,
+            sensor_id: assignment.sensor_id,
+            type_id: measurement.r#type?,
+            ts: measurement.ts,
+            value: self.calibrate(measurement).unwrap_or_else(|| measurement.value.clone()),
+        })
+    }
+}
+
+/// `measurement.created` payload, shaped like a `MeasurementRequest`.
+#[derive(Serialize)]
+struct MeasurementBatch<'a> {
+    measurements: &'a [Measurement],
+}
+
+/// Inserts a chunk of calibrated and QC-checked readings and queues them for a stream position.
+async fn insert_measurement_chunk(conn: &mut PgConnection, chunk: &[Measurement]) -> Result<(), ApiError> {
+    let inventory_numbers: Vec<&str> = chunk.iter().map(|m| m.sensor_inventory_number.as_str()).collect();
+    let values: Vec<BigDecimal> = chunk.iter().map(|m| m.value.clone()).collect();
+    let calibrated_values: Vec<Option<BigDecimal>> = chunk.iter().map(|m| m.calibrated_value.clone()).collect();
+    let timestamps: Vec<NaiveDateTime> = chunk.iter().map(|m| m.ts).collect();
+    let types: Vec<Option<i32>> = chunk.iter().map(|m| m.r#type).collect();
+    let qc_flags: Vec<&str> = chunk.iter().map(|m| m.qc_flag.as_str()).collect();
+    // Postgres cannot unnest a ragged two-dimensional array, so the checks travel comma-joined.
+    let qc_checks: Vec<String> = chunk.iter().map(|m| m.qc_checks.join(",")).collect();
+
+    query(
+        "WITH inserted AS (
+             INSERT INTO measurements (sensor_inventory_number, value, calibrated_value, ts, type, qc_flag, qc_checks)
+             SELECT inventory_number, value, calibrated_value, ts, type, qc_flag,
+                    string_to_array(qc_checks, ',')
+             FROM UNNEST($1::varchar[], $2::numeric[], $3::numeric[], $4::timestamp[], $5::integer[], $6::text[], $7::text[])
+                 AS r(inventory_number, value, calibrated_value, ts, type, qc_flag, qc_checks)
+             RETURNING id
+         )
+         INSERT INTO measurement_stream_pending (measurement_id) SELECT id FROM inserted"
+    )
+        .bind(inventory_numbers)
+        .bind(values)
+        .bind(calibrated_values)
+        .bind(timestamps)
+        .bind(types)
+        .bind(qc_flags)
+        .bind(qc_checks)
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Stores every valid reading of the batch with one multi-row insert per chunk and reports
+/// the rejected ones. Database failures roll back the whole batch. With `station_id` set,
+/// only readings for sensors currently assigned to that station are accepted. Stored readings
+/// carry their QC flag and are checked against the alert rules, queued for `measurement.created` webhooks and
+/// handed to the stream sequencer, which publishes them to live subscribers, once the transaction commits.
+pub async fn insert_measurements(
+    pool: &PgPool,
+    events: &MeasurementEvents,
+    item: &MeasurementRequest,
+    max_batch_size: usize,
+    station_id: Option<i32>,
+) -> Result<MeasurementIngestResponse, ApiError> {
+    if item.measurements.len() > max_batch_size {
+        return Err(ApiError::Validation(format!(
+            "batch of {} readings exceeds the maximum of {}",
+            item.measurements.len(),
+            max_batch_size
+        )));
+    }
+
+    let mut tx = pool.begin().await?;
+    let context = IngestContext::load(&mut tx, &item.measurements, station_id).await?;
+
+    let mut accepted = Vec::with_capacity(item.measurements.len());
+    let mut rejections = Vec::new();
+
+    for (index, measurement) in item.measurements.iter().enumerate() {
+        match context.check(measurement) {
+            Ok(()) => accepted.push(measurement),
+            Err(reason) => rejections.push(MeasurementRejection { index, reason }),
+        }
+    }
+
+    let quality = context.quality_control(&mut tx, &accepted).await?;
+    let stored: Vec<Measurement> = accepted
+        .iter()
+        .zip(quality)
+        .map(|(m, (qc_flag, qc_checks))| Measurement {
+            sensor_inventory_number: m.sensor_inventory_number.clone(),
+            station_id: None,
+            value: m.value.clone(),
+            calibrated_value: context.calibrate(m),
+            units: None,
+            ts: m.ts,
+            r#type: m.r#type,
+            qc_flag,
+            qc_checks: qc_checks.into_iter().map(String::from).collect(),
+            corrected_value: None,
+            qc_note: None,
+            reviewed_by: None,
+            reviewed_at: None,
+        })
+        .collect();
+
+    for chunk in stored.chunks(INSERT_CHUNK_SIZE) {
+        insert_measurement_chunk(&mut tx, chunk).await?;
+    }
+
+    let alert_readings: Vec<AlertReading> = accepted.iter().filter_map(|m| context.alert_reading(m)).collect();
+    if !alert_readings.is_empty() {
+        evaluate_alerts(&mut tx, &alert_readings).await?;
+    }
+
+    if !stored.is_empty() {
+        enqueue_webhook_events(&mut tx, WebhookEvent::MeasurementCreated, || {
+            stored
+                .chunks(WEBHOOK_BATCH_SIZE)
+                .map(|measurements| MeasurementBatch { measurements })
+        })
+            .await?;
+    }
+
+    tx.commit().await?;
+    if !stored.is_empty() {
+        events.notify_stored();
+    }
+
+    Ok(MeasurementIngestResponse {
+        accepted: accepted.len(),
+        rejected: rejections.len(),
+        rejections,
+    })
+}
+
+/// Rejections `insert_measurements` would report for `measurements`, without storing anything.
+pub async fn check_measurements(
+    pool: &PgPool,
+    measurements: &[Measurement],
+    station_id: Option<i32>,
+) -> Result<Vec<MeasurementRejection>, ApiError> {
+    let mut conn = pool.acquire().await?;
+    let context = IngestContext::load(&mut conn, measurements, station_id).await?;
+
+    Ok(measurements
+        .iter()
+        .enumerate()
+        .filter_map(|(index, measurement)| {
+            context.check(measurement).err().map(|reason| MeasurementRejection { index, reason })
+        })
+        .collect())
+}
+
+pub async fn delete_measurement(pool: &PgPool, number: String) -> Result<(), ApiError> {
 
     query!("DELETE FROM measurements WHERE sensor_inventory_number = $1", number)
         .execute(pool)
         .await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Applies the reviews in one transaction, recording each in the review history, and returns the
+/// reviewed readings. Fails without changing anything when a review matches no reading.
+pub async fn review_measurements(
+    pool: &PgPool,
+    reviews: &[MeasurementReview],
+    identity: &Identity,
+) -> Result<Vec<Measurement>, ApiError> {
+    if reviews.is_empty() {
+        return Err(ApiError::Validation(String::from("no readings to review")));
+    }
+
+    let mut tx = pool.begin().await?;
+    let mut ids: Vec<i64> = Vec::with_capacity(reviews.len());
+
+    for review in reviews {
+        let reviewed = query!(
+            "WITH target AS (
+                 SELECT id, qc_flag, corrected_value FROM measurements
+                 WHERE sensor_inventory_number = $1 AND type = $2 AND ts = $3
+                 FOR UPDATE
+             ), logged AS (
+                 INSERT INTO measurement_reviews
+                     (measurement_id, previous_qc_flag, previous_corrected_value, qc_flag, corrected_value,
+                      note, api_key_id, reviewed_by, reviewed_at)
+                 SELECT id, qc_flag, corrected_value, $4, $5, $6, $7, $8, now() FROM target
+             )
+             UPDATE measurements m
+             SET qc_flag = $4, corrected_value = $5, qc_note = $6, reviewed_by = $8, reviewed_at = now()
+             FROM target
+             WHERE m.id = target.id
+             RETURNING m.id",
+            review.sensor_inventory_number,
+            review.r#type,
+            review.ts,
+            review.qc_flag.as_str(),
+            review.corrected_value,
+            review.note,
+            identity.key_id,
+            identity.name
+        )
+            .fetch_all(&mut *tx)
+            .await?;
+
+        if reviewed.is_empty() {
+            return Err(ApiError::NotFound(format!(
+                "no reading of sensor inventory number {} with type {} at {}",
+                review.sensor_inventory_number, review.r#type, review.ts
+            )));
+        }
+        ids.extend(reviewed.into_iter().map(|row| row.id));
+    }
+
+    let mut builder = QueryBuilder::new(MEASUREMENT_SELECT);
+    builder
+        .push(" WHERE m.id = ANY(")
+        .push_bind(&ids)
+        .push(") ORDER BY m.ts, m.sensor_inventory_number, COALESCE(m.type, 0)");
+
+    let rows = builder
+        .build_query_as::<MeasurementRow>()
+        .fetch_all(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    Ok(rows.into_iter().map(Measurement::from).collect())
+}
+
+pub const DEFAULT_REVIEW_LIMIT: i64 = 100;
+pub const MAX_REVIEW_LIMIT: i64 = 1000;
+
+#[derive(FromRow)]
+struct ReviewRow {
+    id: i64,
+    sensor_inventory_number: String,
+    r#type: Option<i32>,
+    ts: NaiveDateTime,
+    value: BigDecimal,
+    previous_qc_flag: String,
+    previous_corrected_value: Option<BigDecimal>,
+    qc_flag: String,
+    corrected_value: Option<BigDecimal>,
+    note: Option<String>,
+    reviewed_by: String,
+    reviewed_at: NaiveDateTime,
+}
+
+impl From<ReviewRow> for MeasurementReviewEntry {
+    fn from(row: ReviewRow) -> Self {
+        MeasurementReviewEntry {
+            id: row.id,
+            sensor_inventory_number: row.sensor_inventory_number,
+            r#type: row.r#type,
+            ts: row.ts,
+            value: row.value,
+            previous_qc_flag: row.previous_qc_flag.parse().unwrap_or_default(),
+            previous_corrected_value: row.previous_corrected_value,
+            qc_flag: row.qc_flag.parse().unwrap_or_default(),
+            corrected_value: row.corrected_value,
+            note: row.note,
+            reviewed_by: row.reviewed_by,
+            reviewed_at: row.reviewed_at,
+        }
+    }
+}
+
+/// Review history, newest first.
+pub async fn fetch_measurement_reviews(
+    pool: &PgPool,
+    review_query: &MeasurementReviewQuery,
+) -> Result<Vec<MeasurementReviewEntry>, ApiError> {
+    let limit = review_query.limit.unwrap_or(DEFAULT_REVIEW_LIMIT).clamp(1, MAX_REVIEW_LIMIT);
+
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT r.id, m.sensor_inventory_number, m.type, m.ts, m.value, r.previous_qc_flag,
+                r.previous_corrected_value, r.qc_flag, r.corrected_value, r.note, r.reviewed_by, r.reviewed_at
+         FROM measurement_reviews r
+         JOIN measurements m ON m.id = r.measurement_id
+         WHERE TRUE",
+    );
+
+    if let Some(inventory_number) = &review_query.inventory_number {
+        builder.push(" AND m.sensor_inventory_number = ").push_bind(inventory_number);
+    }
+    if let Some(type_id) = review_query.r#type {
+        builder.push(" AND m.type = ").push_bind(type_id);
+    }
+    if let Some(from) = review_query.from {
+        builder.push(" AND m.ts >= ").push_bind(from);
+    }
+    if let Some(to) = review_query.to {
+        builder.push(" AND m.ts < ").push_bind(to);
+    }
+
+    builder.push(" ORDER BY r.id DESC LIMIT ").push_bind(limit);
+
+    let rows = builder
+        .build_query_as::<ReviewRow>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.into_iter().map(MeasurementReviewEntry::from).collect())
+}
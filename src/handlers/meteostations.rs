@@ -1,38 +1,397 @@
-use sqlx::{PgPool, query, query_as, Row};
+use std::collections::HashMap;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, query, query_as};
+use crate::error::ApiError;
 use crate::models::*;
 
-pub async fn fetch_meteostations(pool: &PgPool) -> Result<Vec<Meteostation>, sqlx::Error> {
-    let rows = query_as!(
-        Meteostation,
-        "SELECT id, name, longitude, latitude FROM meteostations"
+const MIN_ELEVATION: i32 = -1000;
+const MAX_ELEVATION: i32 = 9000;
+/// Mean Earth radius used for haversine distances.
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+pub const GEOJSON_CONTENT_TYPE: &str = "application/geo+json";
+
+#[derive(FromRow)]
+struct MeteostationRow {
+    id: i32,
+    name: String,
+    longitude: BigDecimal,
+    latitude: BigDecimal,
+    elevation: Option<BigDecimal>,
+    timezone: Option<String>,
+    wmo_index: Option<String>,
+    icao_code: Option<String>,
+    owner: Option<String>,
+    commissioned_on: Option<NaiveDate>,
+    tags: Vec<String>,
+}
+
+impl From<MeteostationRow> for Meteostation {
+    fn from(row: MeteostationRow) -> Self {
+        Meteostation {
+            id: row.id,
+            name: row.name,
+            longitude: row.longitude,
+            latitude: row.latitude,
+            metadata: MeteostationMetadata {
+                elevation: row.elevation,
+                timezone: row.timezone,
+                wmo_index: row.wmo_index,
+                icao_code: row.icao_code,
+                owner: row.owner,
+                commissioned_on: row.commissioned_on,
+                tags: row.tags,
+            },
+            distance_km: None,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct NearbyRow {
+    #[sqlx(flatten)]
+    station: MeteostationRow,
+    distance_km: Option<f64>,
+}
+
+/// Parses `lat,lon` into `(latitude, longitude)`.
+pub fn parse_point(point: &str) -> Result<(f64, f64), ApiError> {
+    let invalid = || ApiError::Validation(format!("invalid point {}, expected lat,lon", point));
+    let (latitude, longitude) = point.split_once(',').ok_or_else(invalid)?;
+    let latitude: f64 = latitude.trim().parse().map_err(|_| invalid())?;
+    let longitude: f64 = longitude.trim().parse().map_err(|_| invalid())?;
+
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(ApiError::Validation(format!("point {} is outside latitude -90..90 or longitude -180..180", point)));
+    }
+
+    Ok((latitude, longitude))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct BoundingBox {
+    pub min_lon: f64,
+    pub min_lat: f64,
+    pub max_lon: f64,
+    pub max_lat: f64,
+}
+
+/// Parses `minLon,minLat,maxLon,maxLat`. A `minLon` above `maxLon` is a box crossing the antimeridian.
+pub fn parse_bbox(bbox: &str) -> Result<BoundingBox, ApiError> {
+    let invalid = || ApiError::Validation(format!("invalid bbox {}, expected minLon,minLat,maxLon,maxLat", bbox));
+    let values = bbox
+        .split(',')
+        .map(|value| value.trim().parse::<f64>().ok().filter(|value| value.is_finite()))
+        .collect::<Option<Vec<f64>>>()
+        .ok_or_else(invalid)?;
+
+    let [min_lon, min_lat, max_lon, max_lat] = values[..] else {
+        return Err(invalid());
+    };
+    let longitudes = -180.0..=180.0;
+    let latitudes = -90.0..=90.0;
+    if !longitudes.contains(&min_lon) || !longitudes.contains(&max_lon)
+        || !latitudes.contains(&min_lat) || !latitudes.contains(&max_lat) || min_lat > max_lat
+    {
+        return Err(invalid());
+    }
+
+    Ok(BoundingBox { min_lon, min_lat, max_lon, max_lat })
+}
+
+/// Checks coordinate and elevation bounds, identifier formats and the timezone name, and returns
+/// the metadata with the ICAO code upper-cased and blank or repeated tags dropped.
+async fn validate_meteostation(pool: &PgPool, station: &MeteostationRequest) -> Result<MeteostationMetadata, ApiError> {
+    if station.longitude < BigDecimal::from(-180) || station.longitude > BigDecimal::from(180) {
+        return Err(ApiError::Validation(String::from("longitude must be between -180 and 180")));
+    }
+    if station.latitude < BigDecimal::from(-90) || station.latitude > BigDecimal::from(90) {
+        return Err(ApiError::Validation(String::from("latitude must be between -90 and 90")));
+    }
+
+    let mut metadata = station.metadata.clone();
+
+    if let Some(elevation) = &metadata.elevation {
+        if *elevation < BigDecimal::from(MIN_ELEVATION) || *elevation > BigDecimal::from(MAX_ELEVATION) {
+            return Err(ApiError::Validation(format!(
+                "elevation must be between {} and {} metres",
+                MIN_ELEVATION, MAX_ELEVATION
+            )));
+        }
+    }
+
+    if let Some(wmo_index) = &metadata.wmo_index {
+        if wmo_index.len() != 5 || !wmo_index.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ApiError::Validation(format!("WMO index {} must be five digits", wmo_index)));
+        }
+    }
+
+    if let Some(icao_code) = &mut metadata.icao_code {
+        icao_code.make_ascii_uppercase();
+        if icao_code.len() != 4 || !icao_code.bytes().all(|b| b.is_ascii_uppercase()) {
+            return Err(ApiError::Validation(format!("ICAO code {} must be four letters", icao_code)));
+        }
+    }
+
+    if let Some(timezone) = &metadata.timezone {
+        let known = query!(
+            r#"SELECT EXISTS(SELECT 1 FROM pg_timezone_names WHERE name = $1) AS "known!""#,
+            timezone
+        )
+            .fetch_one(pool)
+            .await?
+            .known;
+
+        if !known {
+            return Err(ApiError::Validation(format!(
+                "unknown timezone {}, expected an IANA name such as Europe/Moscow",
+                timezone
+            )));
+        }
+    }
+
+    let mut tags: Vec<String> = Vec::with_capacity(metadata.tags.len());
+    for tag in metadata.tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|known| known == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    metadata.tags = tags;
+
+    Ok(metadata)
+}
+
+/// Stations matching the spatial filters of `station_query`, nearest first when it has `near`,
+/// otherwise by ID.
+pub async fn fetch_meteostations(pool: &PgPool, station_query: &MeteostationQuery) -> Result<Vec<Meteostation>, ApiError> {
+    let near = station_query.near.as_deref().map(parse_point).transpose()?;
+    let bbox = station_query.bbox.as_deref().map(parse_bbox).transpose()?;
+
+    if let Some(radius_km) = station_query.radius_km {
+        if near.is_none() {
+            return Err(ApiError::Validation(String::from("radius_km needs near")));
+        }
+        if !radius_km.is_finite() || radius_km <= 0.0 {
+            return Err(ApiError::Validation(String::from("radius_km must be positive")));
+        }
+    }
+
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT * FROM (
+             SELECT id, name, longitude, latitude, elevation, timezone, wmo_index, icao_code, owner, commissioned_on, tags, ",
+    );
+
+    match near {
+        Some((latitude, longitude)) => {
+            builder
+                .push("2 * ")
+                .push_bind(EARTH_RADIUS_KM)
+                .push(" * asin(LEAST(1, sqrt(power(sin(radians(latitude::float8 - ")
+                .push_bind(latitude)
+                .push(") / 2), 2) + cos(radians(")
+                .push_bind(latitude)
+                .push(")) * cos(radians(latitude::float8)) * power(sin(radians(longitude::float8 - ")
+                .push_bind(longitude)
+                .push(") / 2), 2))))");
+        }
+        None => {
+            builder.push("NULL::float8");
+        }
+    }
+
+    builder.push(" AS distance_km FROM meteostations) s WHERE TRUE");
+
+    if let Some(radius_km) = station_query.radius_km {
+        builder.push(" AND distance_km <= ").push_bind(radius_km);
+    }
+
+    if let Some(bbox) = bbox {
+        builder
+            .push(" AND latitude::float8 BETWEEN ")
+            .push_bind(bbox.min_lat)
+            .push(" AND ")
+            .push_bind(bbox.max_lat);
+
+        if bbox.min_lon <= bbox.max_lon {
+            builder
+                .push(" AND longitude::float8 BETWEEN ")
+                .push_bind(bbox.min_lon)
+                .push(" AND ")
+                .push_bind(bbox.max_lon);
+        } else {
+            builder
+                .push(" AND (longitude::float8 >= ")
+                .push_bind(bbox.min_lon)
+                .push(" OR longitude::float8 <= ")
+                .push_bind(bbox.max_lon)
+                .push(")");
+        }
+    }
+
+    if let Some(operating) = station_query.operating {
+        builder
+            .push(if operating { " AND EXISTS" } else { " AND NOT EXISTS" })
+            .push(
+                " (SELECT 1 FROM meteostations_sensors ms
+                   WHERE ms.station_id = s.id
+                     AND (ms.added_ts IS NULL OR ms.added_ts <= now())
+                     AND (ms.removed_ts IS NULL OR ms.removed_ts > now()))",
+            );
+    }
+
+    builder.push(if near.is_some() { " ORDER BY distance_km, id" } else { " ORDER BY id" });
+
+    if let Some(limit) = station_query.limit {
+        builder.push(" LIMIT ").push_bind(limit.max(1));
+    }
+
+    let rows = builder
+        .build_query_as::<NearbyRow>()
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Meteostation {
+            distance_km: row.distance_km.map(|distance| (distance * 1000.0).round() / 1000.0),
+            ..row.station.into()
+        })
+        .collect())
+}
+
+/// GeoJSON Point feature of a station.
+pub fn station_feature(
+    station: Meteostation,
+    sensors: Vec<MeteostationSensorResponse>,
+    latest: Option<Vec<LatestReading>>,
+) -> StationFeature {
+    // Through the decimal string: `BigDecimal::to_f64` is not correctly rounded.
+    let coordinate = |value: &BigDecimal| value.to_string().parse::<f64>().unwrap_or_default();
+    let longitude = coordinate(&station.longitude);
+    let latitude = coordinate(&station.latitude);
+
+    StationFeature {
+        r#type: String::from("Feature"),
+        id: station.id,
+        geometry: PointGeometry {
+            r#type: String::from("Point"),
+            coordinates: [longitude, latitude],
+        },
+        properties: StationProperties {
+            name: station.name,
+            metadata: station.metadata,
+            distance_km: station.distance_km,
+            sensors,
+            latest,
+        },
+    }
+}
+
+/// The stations of `fetch_meteostations` as a GeoJSON FeatureCollection with their installed
+/// sensors and, when `latest` is set, the newest non-`bad` reading of each measurement type.
+pub async fn fetch_meteostation_features(
+    pool: &PgPool,
+    station_query: &MeteostationQuery,
+) -> Result<StationFeatureCollection, ApiError> {
+    let stations = fetch_meteostations(pool, station_query).await?;
+    let station_ids: Vec<i32> = stations.iter().map(|station| station.id).collect();
+
+    let mut sensors: HashMap<i32, Vec<MeteostationSensorResponse>> = HashMap::new();
+    let rows = query!(
+        "SELECT ms.station_id, ms.inventory_number, ms.sensor_id, s.name AS sensor_name, ms.added_ts, ms.removed_ts
+         FROM meteostations_sensors ms
+         JOIN sensors s ON s.id = ms.sensor_id
+         WHERE ms.station_id = ANY($1)
+           AND (ms.added_ts IS NULL OR ms.added_ts <= now())
+           AND (ms.removed_ts IS NULL OR ms.removed_ts > now())
+         ORDER BY ms.inventory_number",
+        &station_ids
     )
         .fetch_all(pool)
         .await?;
 
-    Ok(rows)
+    for row in rows {
+        sensors.entry(row.station_id).or_default().push(MeteostationSensorResponse {
+            sensor_inventory_number: row.inventory_number,
+            sensor_id: row.sensor_id,
+            sensor_name: row.sensor_name,
+            sensor_added_ts: row.added_ts,
+            sensor_remove_ts: row.removed_ts,
+        });
+    }
+
+    let mut latest: Option<HashMap<i32, Vec<LatestReading>>> = None;
+    if station_query.latest.unwrap_or(false) {
+        let rows = query!(
+            // One index probe per sensor and type, then the newest of the station's sensors.
+            r#"SELECT DISTINCT ON (ms.station_id, mt.id)
+                   ms.station_id, mt.id AS type_id, mt.name AS type_name, mt.units,
+                   m.value AS "value!", m.ts AS "ts!", m.sensor_inventory_number AS "sensor_inventory_number!",
+                   m.qc_flag AS "qc_flag!"
+               FROM meteostations_sensors ms
+               CROSS JOIN measurements_type mt
+               CROSS JOIN LATERAL (
+                   SELECT COALESCE(m.corrected_value, m.calibrated_value, m.value) AS value,
+                          m.ts, m.sensor_inventory_number, m.qc_flag
+                   FROM measurements m
+                   WHERE m.sensor_inventory_number = ms.inventory_number AND m.type = mt.id AND m.qc_flag <> 'bad'
+                   ORDER BY m.ts DESC
+                   LIMIT 1
+               ) m
+               WHERE ms.station_id = ANY($1)
+               ORDER BY ms.station_id, mt.id, m.ts DESC"#,
+            &station_ids
+        )
+            .fetch_all(pool)
+            .await?;
+
+        let mut readings: HashMap<i32, Vec<LatestReading>> = HashMap::new();
+        for row in rows {
+            readings.entry(row.station_id).or_default().push(LatestReading {
+                type_id: row.type_id,
+                type_name: row.type_name,
+                units: row.units,
+                value: row.value,
+                ts: row.ts,
+                sensor_inventory_number: row.sensor_inventory_number,
+                qc_flag: row.qc_flag.parse()?,
+            });
+        }
+        latest = Some(readings);
+    }
+
+    let features = stations
+        .into_iter()
+        .map(|station| {
+            let station_sensors = sensors.remove(&station.id).unwrap_or_default();
+            let station_latest = latest.as_mut().map(|latest| latest.remove(&station.id).unwrap_or_default());
+            station_feature(station, station_sensors, station_latest)
+        })
+        .collect();
+
+    Ok(StationFeatureCollection {
+        r#type: String::from("FeatureCollection"),
+        features,
+    })
 }
 
-pub async fn fetch_meteostation(pool: &PgPool, station_id: i32) -> Result<Meteostation, sqlx::Error> {
+pub async fn fetch_meteostation(pool: &PgPool, station_id: i32) -> Result<Meteostation, ApiError> {
     let station = query_as!(
-        Meteostation,
+        MeteostationRow,
         r#"
-        SELECT id, name, longitude, latitude
+        SELECT id, name, longitude, latitude, elevation, timezone, wmo_index, icao_code, owner, commissioned_on, tags
         FROM meteostations
         WHERE id = $1
         "#, station_id
     )
-        .fetch_one(pool)
-        .await?;
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("meteostation {} not found", station_id)))?;
 
-    Ok(Meteostation {
-        id: station.id,
-        name: station.name,
-        longitude: station.longitude,
-        latitude: station.latitude
-    })
+    Ok(station.into())
 }
 
-pub async fn fetch_sensor_meteostation(pool: &PgPool, station_id: i32) -> Result<Vec<Sensor>, sqlx::Error> {
+pub async fn fetch_sensor_meteostation(pool: &PgPool, station_id: i32) -> Result<Vec<Sensor>, ApiError> {
 
     let sensors = query_as!(
         Sensor,
@@ -50,60 +409,88 @@ pub async fn fetch_sensor_meteostation(pool: &PgPool, station_id: i32) -> Result
     Ok(sensors)
 }
 
-pub async fn insert_meteostation(pool: &PgPool, station: &MeteostationRequest) -> Result<Meteostation, sqlx::Error> {
-    let station_id = sqlx::query(
-        "INSERT INTO meteostations (name, longitude, latitude) VALUES ($1, $2, $3) RETURNING id")
-        .bind(&station.name)
-        .bind(&station.longitude)
-        .bind(&station.latitude)
+pub async fn insert_meteostation(pool: &PgPool, station: &MeteostationRequest) -> Result<Meteostation, ApiError> {
+    let metadata = validate_meteostation(pool, station).await?;
+
+    let row = query_as!(
+        MeteostationRow,
+        "INSERT INTO meteostations
+             (name, longitude, latitude, elevation, timezone, wmo_index, icao_code, owner, commissioned_on, tags)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+         RETURNING id, name, longitude, latitude, elevation, timezone, wmo_index, icao_code, owner, commissioned_on, tags",
+        station.name,
+        station.longitude,
+        station.latitude,
+        metadata.elevation,
+        metadata.timezone,
+        metadata.wmo_index,
+        metadata.icao_code,
+        metadata.owner,
+        metadata.commissioned_on,
+        &metadata.tags
+    )
         .fetch_one(pool)
-        .await?
-        .try_get(0)?;
+        .await?;
 
-    Ok(Meteostation {
-        id: station_id,
-        name: station.name.clone(),
-        longitude: station.longitude.clone(),
-        latitude: station.latitude.clone()
-    })
+    Ok(row.into())
 }
 
-pub async fn update_one_station(pool: &PgPool, station_id: i32, station: &MeteostationRequest) -> Result<Meteostation, sqlx::Error> {
-    query_as!(
-        Meteostation,
+pub async fn update_one_station(pool: &PgPool, station_id: i32, station: &MeteostationRequest) -> Result<Meteostation, ApiError> {
+    let metadata = validate_meteostation(pool, station).await?;
+
+    let row = query_as!(
+        MeteostationRow,
         r#"
         UPDATE meteostations
         SET name = COALESCE($1, name),
             longitude = COALESCE($2, longitude),
-            latitude = COALESCE($3, latitude)
-        WHERE id = $4
-        RETURNING id, name, longitude, latitude
+            latitude = COALESCE($3, latitude),
+            elevation = $4,
+            timezone = $5,
+            wmo_index = $6,
+            icao_code = $7,
+            owner = $8,
+            commissioned_on = $9,
+            tags = $10
+        WHERE id = $11
+        RETURNING id, name, longitude, latitude, elevation, timezone, wmo_index, icao_code, owner, commissioned_on, tags
         "#,
         station.name,
         station.longitude,
         station.latitude,
+        metadata.elevation,
+        metadata.timezone,
+        metadata.wmo_index,
+        metadata.icao_code,
+        metadata.owner,
+        metadata.commissioned_on,
+        &metadata.tags,
         station_id
     )
         .fetch_one(pool)
         .await?;
 
-    fetch_meteostation(pool, station_id).await
+    Ok(row.into())
 }
 
-pub async fn delete_one_station(pool: &PgPool, station_id: i32) -> Result<(), sqlx::Error> {
+pub async fn delete_one_station(pool: &PgPool, station_id: i32) -> Result<(), ApiError> {
     let count: Option<i64> = query!("SELECT COUNT(*) as count FROM meteostations_sensors WHERE station_id = $1", station_id)
         .fetch_one(pool)
         .await?
         .count;
 
     if count > Some(0) {
-        return Err(sqlx::Error::RowNotFound);
+        return Err(ApiError::Conflict(String::from("meteostation has sensors in meteostations_sensors")));
     }
 
-    query!("DELETE FROM meteostations WHERE id = $1", station_id)
+    let result = query!("DELETE FROM meteostations WHERE id = $1", station_id)
         .execute(pool)
         .await?;
 
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("meteostation {} not found", station_id)));
+    }
+
     Ok(())
 
 }
\ No newline at end of file
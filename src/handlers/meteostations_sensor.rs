@@ -1,13 +1,17 @@
 use std::collections::HashMap;
 use chrono::{Utc};
-use sqlx::{PgPool, query};
-use crate::models::{MeteostationResponse, MeteostationSensorResponse, MeteostationSensorCreateRequest, MeteostationSensorRemove};
+use sqlx::{PgPool, query, query_as};
+use crate::delivery::WebhookEvent;
+use crate::error::ApiError;
+use crate::handlers::webhooks::enqueue_webhook_event;
+use crate::models::{MeteostationMetadata, MeteostationResponse, MeteostationSensor, MeteostationSensorResponse, MeteostationSensorCreateRequest, MeteostationSensorRemove};
 
 pub async fn fetch_meteostation_sensors(
     pool: &PgPool,
-) -> Result<Vec<MeteostationResponse>, sqlx::Error> {
+) -> Result<Vec<MeteostationResponse>, ApiError> {
     let meteostations = query!(
         "SELECT m.id AS station_id, m.name AS station_name, m.longitude AS station_longitude, m.latitude AS station_latitude,
+                m.elevation, m.timezone, m.wmo_index, m.icao_code, m.owner, m.commissioned_on, m.tags,
                 ms.inventory_number, ms.sensor_id, s.name AS sensor_name, ms.added_ts AS sensor_added_ts, ms.removed_ts AS sensor_remove_ts
          FROM meteostations m
          JOIN meteostations_sensors ms ON m.id = ms.station_id
@@ -34,6 +38,15 @@ pub async fn fetch_meteostation_sensors(
                 station_name: record.station_name,
                 station_longitude: record.station_longitude,
                 station_latitude: record.station_latitude,
+                metadata: MeteostationMetadata {
+                    elevation: record.elevation,
+                    timezone: record.timezone,
+                    wmo_index: record.wmo_index,
+                    icao_code: record.icao_code,
+                    owner: record.owner,
+                    commissioned_on: record.commissioned_on,
+                    tags: record.tags,
+                },
                 sensors: vec![],
             })
             .sensors
@@ -48,19 +61,24 @@ pub async fn fetch_meteostation_sensors(
 pub async fn insert_meteostation_sensors(
     pool: &PgPool,
     item: &MeteostationSensorCreateRequest
-) -> Result<(), sqlx::Error> {
+) -> Result<(), ApiError> {
 
-    for sensor in &item.meteostations_sensors {
+    let mut tx = pool.begin().await?;
+
+    for (index, sensor) in item.meteostations_sensors.iter().enumerate() {
         query!(
             "INSERT INTO meteostations_sensors (station_id, sensor_id, added_ts) VALUES ($1, $2, $3)",
             sensor.station_id,
             sensor.sensor_id,
             sensor.added_ts.unwrap_or_else(|| Utc::now().naive_utc())
         )
-            .execute(pool)
-            .await?;
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| ApiError::from(err).at_item(index))?;
     }
 
+    tx.commit().await?;
+
     Ok(())
 }
 
@@ -68,15 +86,24 @@ pub async fn remove_meteostation_sensor(
     pool: &PgPool,
     number: String,
     item: &MeteostationSensorRemove
-) -> Result<(), sqlx::Error> {
+) -> Result<(), ApiError> {
 
-    query!(
-        "UPDATE meteostations_sensors SET removed_ts = $1 WHERE inventory_number = $2",
+    let mut tx = pool.begin().await?;
+
+    let removed = query_as!(
+        MeteostationSensor,
+        r#"UPDATE meteostations_sensors SET removed_ts = $1 WHERE inventory_number = $2
+           RETURNING inventory_number, station_id, sensor_id, added_ts AS "added_ts!", removed_ts"#,
         item.removed_ts.unwrap_or_else(|| Utc::now().naive_utc()),
         number
     )
-        .execute(pool)
-        .await?;
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("sensor inventory number {} not found", number)))?;
+
+    enqueue_webhook_event(&mut tx, WebhookEvent::SensorRemoved, || removed).await?;
+
+    tx.commit().await?;
 
     Ok(())
-}
\ No newline at end of file
+}
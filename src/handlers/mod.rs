@@ -4,6 +4,12 @@ pub mod meteostations;
 pub mod sensors_measurements;
 pub mod meteostations_sensor;
 pub mod measurements;
+pub mod api_keys;
+pub mod alerts;
+pub mod webhooks;
+pub mod derived_measurements;
+pub mod measurement_export;
+pub mod measurement_import;
 
 // pub use sensors::*;
 // pub use measurement_type::*;
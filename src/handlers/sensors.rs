@@ -1,7 +1,9 @@
 use sqlx::{PgPool, query, query_as, Row};
+use crate::error::ApiError;
+use crate::formula::{validate_formulas, DEFAULT_FORMULA};
 use crate::models::{SensorResponse, SensorMeasurementResponse, SensorRequest};
 
-pub async fn fetch_sensors(pool: &PgPool) -> Result<Vec<SensorResponse>, sqlx::Error> {
+pub async fn fetch_sensors(pool: &PgPool) -> Result<Vec<SensorResponse>, ApiError> {
     let sensors = query!("SELECT id as sensor_id, name as sensor_name FROM sensors")
         .fetch_all(pool)
         .await?;
@@ -37,7 +39,7 @@ pub async fn fetch_sensors(pool: &PgPool) -> Result<Vec<SensorResponse>, sqlx::E
     Ok(sensor_responses)
 }
 
-pub async fn fetch_sensor(pool: &PgPool, sensor_id: i32) -> Result<SensorResponse, sqlx::Error> {
+pub async fn fetch_sensor(pool: &PgPool, sensor_id: i32) -> Result<SensorResponse, ApiError> {
     let sensor = query!("SELECT id as sensor_id, name as sensor_name FROM sensors WHERE id = $1", sensor_id)
         .fetch_one(pool)
         .await?;
@@ -67,7 +69,7 @@ pub async fn fetch_sensor(pool: &PgPool, sensor_id: i32) -> Result<SensorRespons
     })
 }
 
-pub async fn fetch_sensor_types(pool: &PgPool, sensor_id: i32, ) -> Result<Vec<SensorMeasurementResponse>, sqlx::Error> {
+pub async fn fetch_sensor_types(pool: &PgPool, sensor_id: i32, ) -> Result<Vec<SensorMeasurementResponse>, ApiError> {
     let rows = query!(
         "SELECT mt.id as type_id, mt.name as type_name, mt.units as type_units, sm.measurment_formula as type_formula
          FROM sensors_measurements sm
@@ -91,15 +93,20 @@ pub async fn fetch_sensor_types(pool: &PgPool, sensor_id: i32, ) -> Result<Vec<S
     Ok(sensor_types)
 }
 
-pub async fn insert_sensor(pool: &PgPool, new_sensor: &SensorRequest, ) -> Result<SensorResponse, sqlx::Error> {
+pub async fn insert_sensor(pool: &PgPool, new_sensor: &SensorRequest, ) -> Result<SensorResponse, ApiError> {
+    validate_formulas(new_sensor.sensors_measurements.iter().map(|m| m.type_formula.as_deref()))
+        .map_err(|(index, err)| ApiError::from(err).at_item(index))?;
+
+    let mut tx = pool.begin().await?;
+
     let sensor_id: i32 = query("INSERT INTO sensors (name) VALUES ($1) RETURNING id")
         .bind(&new_sensor.sensor_name)
-        .fetch_one(pool)
+        .fetch_one(&mut *tx)
         .await?
         .try_get(0)?;
 
-    for measurement in &new_sensor.sensors_measurements {
-        let type_formula = measurement.type_formula.clone().unwrap_or_else(|| "value".to_string());
+    for (index, measurement) in new_sensor.sensors_measurements.iter().enumerate() {
+        let type_formula = measurement.type_formula.clone().unwrap_or_else(|| DEFAULT_FORMULA.to_string());
 
         query(
             "INSERT INTO sensors_measurements (sensor_id, type_id, measurment_formula) VALUES ($1, $2, $3)"
@@ -107,10 +114,13 @@ pub async fn insert_sensor(pool: &PgPool, new_sensor: &SensorRequest, ) -> Resul
             .bind(sensor_id)
             .bind(measurement.type_id)
             .bind(type_formula)
-            .execute(pool)
-            .await?;
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| ApiError::from(err).at_item(index))?;
     }
 
+    tx.commit().await?;
+
     let mut response_measurements = Vec::new();
 
     for measurement in &new_sensor.sensors_measurements {
@@ -125,7 +135,7 @@ pub async fn insert_sensor(pool: &PgPool, new_sensor: &SensorRequest, ) -> Resul
                 type_id: measurement.type_id,
                 type_name: row.0,
                 type_units: row.1,
-                type_formula: measurement.type_formula.clone().unwrap_or_else(|| "value".to_string()),
+                type_formula: measurement.type_formula.clone().unwrap_or_else(|| DEFAULT_FORMULA.to_string()),
             });
         }
     }
@@ -137,20 +147,29 @@ pub async fn insert_sensor(pool: &PgPool, new_sensor: &SensorRequest, ) -> Resul
     })
 }
 
-pub async fn update_one_sensor(pool: &PgPool, sensor_id: i32, update_sensor: &SensorRequest) -> Result<SensorResponse, sqlx::Error> {
-    query("UPDATE sensors SET name = $1 WHERE id = $2")
+pub async fn update_one_sensor(pool: &PgPool, sensor_id: i32, update_sensor: &SensorRequest) -> Result<SensorResponse, ApiError> {
+    validate_formulas(update_sensor.sensors_measurements.iter().map(|m| m.type_formula.as_deref()))
+        .map_err(|(index, err)| ApiError::from(err).at_item(index))?;
+
+    let mut tx = pool.begin().await?;
+
+    let result = query("UPDATE sensors SET name = $1 WHERE id = $2")
         .bind(&update_sensor.sensor_name)
         .bind(sensor_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
 
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("sensor {} not found", sensor_id)));
+    }
+
     query("DELETE FROM sensors_measurements WHERE sensor_id = $1")
         .bind(sensor_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
 
-    for measurement in &update_sensor.sensors_measurements {
-        let type_formula = measurement.type_formula.clone().unwrap_or_else(|| "value".to_string());
+    for (index, measurement) in update_sensor.sensors_measurements.iter().enumerate() {
+        let type_formula = measurement.type_formula.clone().unwrap_or_else(|| DEFAULT_FORMULA.to_string());
 
         query(
             "INSERT INTO sensors_measurements (sensor_id, type_id, measurment_formula) VALUES ($1, $2, $3)"
@@ -158,30 +177,41 @@ pub async fn update_one_sensor(pool: &PgPool, sensor_id: i32, update_sensor: &Se
             .bind(sensor_id)
             .bind(measurement.type_id)
             .bind(type_formula)
-            .execute(pool)
-            .await?;
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| ApiError::from(err).at_item(index))?;
     }
 
+    tx.commit().await?;
+
     fetch_sensor(pool, sensor_id).await
 }
 
-pub async fn delete_one_sensor(pool: &PgPool, sensor_id: i32) -> Result<(), sqlx::Error> {
+pub async fn delete_one_sensor(pool: &PgPool, sensor_id: i32) -> Result<(), ApiError> {
     let count: Option<i64> = query!("SELECT COUNT(*) as count FROM meteostations_sensors WHERE sensor_id = $1", sensor_id)
         .fetch_one(pool)
         .await?
         .count;
 
     if count > Some(0) {
-        return Err(sqlx::Error::RowNotFound);
+        return Err(ApiError::Conflict(String::from("sensor is assigned in meteostations_sensors")));
     }
 
+    let mut tx = pool.begin().await?;
+
     query!("DELETE FROM sensors_measurements WHERE sensor_id = $1", sensor_id)
-        .execute(pool)
+        .execute(&mut *tx)
         .await?;
 
-    query!("DELETE FROM sensors WHERE id = $1", sensor_id)
-        .execute(pool)
+    let result = query!("DELETE FROM sensors WHERE id = $1", sensor_id)
+        .execute(&mut *tx)
         .await?;
 
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("sensor {} not found", sensor_id)));
+    }
+
+    tx.commit().await?;
+
     Ok(())
 }
\ No newline at end of file
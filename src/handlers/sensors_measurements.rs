@@ -1,22 +1,32 @@
 use sqlx::{PgPool};
+use crate::error::ApiError;
+use crate::formula::{validate_formulas, DEFAULT_FORMULA};
 use crate::models::{SensorMeasurementRequest, SensorMeasurementsDelete};
 
 pub async fn insert_sensor_measurements(
     pool: &PgPool,
     sensor_id: i32,
     item: &SensorMeasurementRequest,
-) -> Result<(), sqlx::Error> {
-    for measurement in &item.sensors_measurements {
+) -> Result<(), ApiError> {
+    validate_formulas(item.sensors_measurements.iter().map(|m| m.measurement_formula.as_deref()))
+        .map_err(|(index, err)| ApiError::from(err).at_item(index))?;
+
+    let mut tx = pool.begin().await?;
+
+    for (index, measurement) in item.sensors_measurements.iter().enumerate() {
         sqlx::query!(
             "INSERT INTO sensors_measurements (sensor_id, type_id, measurment_formula) VALUES ($1, $2, $3)",
             sensor_id,
             measurement.type_id,
-            measurement.measurement_formula
+            measurement.measurement_formula.as_deref().unwrap_or(DEFAULT_FORMULA)
         )
-            .execute(pool)
-            .await?;
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| ApiError::from(err).at_item(index))?;
     }
 
+    tx.commit().await?;
+
     Ok(())
 }
 
@@ -24,9 +34,7 @@ pub async fn delete_many_sensor_measurements(
     pool: &PgPool,
     sensor_id: i32,
     item: &SensorMeasurementsDelete,
-) -> Result<(), sqlx::Error> {
-    let sensor_id = sensor_id;
-
+) -> Result<(), ApiError> {
     for type_id in &item.measurements_type {
         sqlx::query!(
             "DELETE FROM sensors_measurements WHERE sensor_id = $1 AND type_id = $2",
@@ -0,0 +1,319 @@
+use std::time::Duration;
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder, query};
+use crate::delivery::{generate_webhook_secret, WebhookEvent};
+use crate::error::ApiError;
+use crate::models::{Webhook, WebhookCreated, WebhookDelivery, WebhookDeliveryQuery, WebhookRequest};
+
+pub const DEFAULT_DELIVERY_LIMIT: i64 = 100;
+pub const MAX_DELIVERY_LIMIT: i64 = 1000;
+
+fn validate_webhook(item: &WebhookRequest) -> Result<(), ApiError> {
+    if !item.url.starts_with("http://") && !item.url.starts_with("https://") {
+        return Err(ApiError::Validation(String::from("webhook url must be an http:// or https:// URL")));
+    }
+    if item.event_types.is_empty() {
+        return Err(ApiError::Validation(String::from("a webhook needs at least one event type")));
+    }
+    if item.secret.as_deref().is_some_and(str::is_empty) {
+        return Err(ApiError::Validation(String::from("webhook secret must not be empty")));
+    }
+
+    Ok(())
+}
+
+fn event_names(events: &[WebhookEvent]) -> Vec<&'static str> {
+    events.iter().map(WebhookEvent::as_str).collect()
+}
+
+pub async fn fetch_webhooks(pool: &PgPool) -> Result<Vec<Webhook>, ApiError> {
+    let rows = query!("SELECT id, url, event_types, enabled, created_at FROM webhooks ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Webhook {
+            id: row.id,
+            url: row.url,
+            event_types: row.event_types.iter().filter_map(|event| event.parse().ok()).collect(),
+            enabled: row.enabled,
+            created_at: row.created_at,
+        })
+        .collect())
+}
+
+pub async fn insert_webhook(pool: &PgPool, item: &WebhookRequest) -> Result<WebhookCreated, ApiError> {
+    validate_webhook(item)?;
+
+    let secret = item.secret.clone().unwrap_or_else(generate_webhook_secret);
+    let enabled = item.enabled.unwrap_or(true);
+
+    let id = query!(
+        "INSERT INTO webhooks (url, event_types, secret, enabled) VALUES ($1, $2, $3, $4) RETURNING id",
+        item.url,
+        &event_names(&item.event_types) as &[&str],
+        secret,
+        enabled
+    )
+        .fetch_one(pool)
+        .await?
+        .id;
+
+    Ok(WebhookCreated {
+        id,
+        url: item.url.clone(),
+        event_types: item.event_types.clone(),
+        enabled,
+        secret,
+    })
+}
+
+pub async fn update_one_webhook(pool: &PgPool, webhook_id: i32, item: &WebhookRequest) -> Result<Webhook, ApiError> {
+    validate_webhook(item)?;
+
+    let row = query!(
+        "UPDATE webhooks
+         SET url = $1, event_types = $2, secret = COALESCE($3, secret), enabled = $4
+         WHERE id = $5
+         RETURNING created_at",
+        item.url,
+        &event_names(&item.event_types) as &[&str],
+        item.secret,
+        item.enabled.unwrap_or(true),
+        webhook_id
+    )
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("webhook {} not found", webhook_id)))?;
+
+    Ok(Webhook {
+        id: webhook_id,
+        url: item.url.clone(),
+        event_types: item.event_types.clone(),
+        enabled: item.enabled.unwrap_or(true),
+        created_at: row.created_at,
+    })
+}
+
+pub async fn delete_one_webhook(pool: &PgPool, webhook_id: i32) -> Result<(), ApiError> {
+    let result = query!("DELETE FROM webhooks WHERE id = $1", webhook_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(ApiError::NotFound(format!("webhook {} not found", webhook_id)));
+    }
+
+    Ok(())
+}
+
+/// Delivery log of a webhook, newest first.
+pub async fn fetch_webhook_deliveries(
+    pool: &PgPool,
+    webhook_id: i32,
+    delivery_query: &WebhookDeliveryQuery,
+) -> Result<Vec<WebhookDelivery>, ApiError> {
+    query!("SELECT id FROM webhooks WHERE id = $1", webhook_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("webhook {} not found", webhook_id)))?;
+
+    let limit = delivery_query.limit.unwrap_or(DEFAULT_DELIVERY_LIMIT).clamp(1, MAX_DELIVERY_LIMIT);
+
+    let mut builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT id, webhook_id, event_type, payload, status, attempts, next_attempt_at, last_status_code,
+                last_error, created_at, delivered_at
+         FROM webhook_deliveries WHERE webhook_id = ",
+    );
+    builder.push_bind(webhook_id);
+
+    if let Some(status) = delivery_query.status {
+        builder.push(" AND status = ").push_bind(status.as_str());
+    }
+
+    builder.push(" ORDER BY id DESC LIMIT ").push_bind(limit);
+
+    let rows = builder
+        .build_query_as::<DeliveryRow>()
+        .fetch_all(pool)
+        .await?;
+
+    rows.into_iter().map(WebhookDelivery::try_from).collect()
+}
+
+#[derive(sqlx::FromRow)]
+struct DeliveryRow {
+    id: i64,
+    webhook_id: i32,
+    event_type: String,
+    payload: serde_json::Value,
+    status: String,
+    attempts: i32,
+    next_attempt_at: chrono::NaiveDateTime,
+    last_status_code: Option<i32>,
+    last_error: Option<String>,
+    created_at: chrono::NaiveDateTime,
+    delivered_at: Option<chrono::NaiveDateTime>,
+}
+
+impl TryFrom<DeliveryRow> for WebhookDelivery {
+    type Error = ApiError;
+
+    fn try_from(row: DeliveryRow) -> Result<Self, Self::Error> {
+        Ok(WebhookDelivery {
+            id: row.id,
+            webhook_id: row.webhook_id,
+            event_type: row.event_type,
+            payload: row.payload,
+            status: row.status.parse()?,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_status_code: row.last_status_code,
+            last_error: row.last_error,
+            created_at: row.created_at,
+            delivered_at: row.delivered_at,
+        })
+    }
+}
+
+/// Queues the payload for every enabled webhook subscribed to `event`; it is only built when
+/// there is one. Call it inside the transaction that produced the event, so deliveries exist
+/// exactly when the change commits.
+pub async fn enqueue_webhook_event<T: Serialize>(
+    conn: &mut PgConnection,
+    event: WebhookEvent,
+    payload: impl FnOnce() -> T,
+) -> Result<(), ApiError> {
+    enqueue_webhook_events(conn, event, || [payload()]).await
+}
+
+/// Like `enqueue_webhook_event`, queueing one delivery per payload, in order.
+pub async fn enqueue_webhook_events<T: Serialize, I: IntoIterator<Item = T>>(
+    conn: &mut PgConnection,
+    event: WebhookEvent,
+    payloads: impl FnOnce() -> I,
+) -> Result<(), ApiError> {
+    let subscribed = query!(
+        r#"SELECT EXISTS(SELECT 1 FROM webhooks WHERE enabled AND $1 = ANY(event_types)) AS "subscribed!""#,
+        event.as_str()
+    )
+        .fetch_one(&mut *conn)
+        .await?
+        .subscribed;
+
+    if !subscribed {
+        return Ok(());
+    }
+
+    let payloads = payloads()
+        .into_iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| ApiError::Internal(err.to_string()))?;
+
+    query!(
+        "INSERT INTO webhook_deliveries (webhook_id, event_type, payload)
+         SELECT w.id, $1, p.payload
+         FROM webhooks w CROSS JOIN UNNEST($2::jsonb[]) WITH ORDINALITY AS p(payload, n)
+         WHERE w.enabled AND $1 = ANY(w.event_types)
+         ORDER BY p.n, w.id",
+        event.as_str(),
+        &payloads
+    )
+        .execute(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// A pending delivery claimed by the worker.
+pub struct DueDelivery {
+    pub id: i64,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+/// Claims up to `limit` due deliveries of enabled webhooks. Claimed deliveries are leased for
+/// `lease`, so another worker picks them up again if this one dies mid-attempt.
+pub async fn claim_due_deliveries(pool: &PgPool, limit: i64, lease: Duration) -> Result<Vec<DueDelivery>, ApiError> {
+    let rows = query!(
+        "UPDATE webhook_deliveries d
+         SET next_attempt_at = now() + make_interval(secs => $2)
+         FROM webhooks w
+         WHERE w.id = d.webhook_id AND d.id IN (
+             SELECT dd.id FROM webhook_deliveries dd
+             JOIN webhooks ww ON ww.id = dd.webhook_id
+             WHERE dd.status = 'pending' AND dd.next_attempt_at <= now() AND ww.enabled
+             ORDER BY dd.next_attempt_at
+             LIMIT $1
+             FOR UPDATE OF dd SKIP LOCKED
+         )
+         RETURNING d.id, d.event_type, d.payload, d.attempts, w.url, w.secret",
+        limit,
+        lease.as_secs_f64()
+    )
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| DueDelivery {
+            id: row.id,
+            event_type: row.event_type,
+            payload: row.payload,
+            attempts: row.attempts,
+            url: row.url,
+            secret: row.secret,
+        })
+        .collect())
+}
+
+/// Records one attempt: a 2xx response delivers, anything else schedules a retry in `retry_in`
+/// until `max_attempts` is reached and the delivery fails.
+pub async fn record_delivery_result(
+    pool: &PgPool,
+    delivery_id: i64,
+    result: Result<u16, String>,
+    max_attempts: i32,
+    retry_in: Duration,
+) -> Result<(), ApiError> {
+    let (status_code, error) = match result {
+        Ok(status) if (200..300).contains(&status) => {
+            query!(
+                "UPDATE webhook_deliveries
+                 SET status = 'delivered', attempts = attempts + 1, last_status_code = $2,
+                     last_error = NULL, delivered_at = now()
+                 WHERE id = $1",
+                delivery_id,
+                i32::from(status)
+            )
+                .execute(pool)
+                .await?;
+
+            return Ok(());
+        }
+        Ok(status) => (Some(i32::from(status)), format!("receiver responded with status {}", status)),
+        Err(err) => (None, err),
+    };
+
+    query!(
+        "UPDATE webhook_deliveries
+         SET attempts = attempts + 1, last_status_code = $2, last_error = $3,
+             status = CASE WHEN attempts + 1 >= $4 THEN 'failed' ELSE 'pending' END,
+             next_attempt_at = now() + make_interval(secs => $5)
+         WHERE id = $1",
+        delivery_id,
+        status_code,
+        error,
+        max_attempts,
+        retry_in.as_secs_f64()
+    )
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -2,14 +2,27 @@ mod models;
 mod routes;
 use routes::*;
 mod handlers;
+mod alerting;
+mod auth;
 mod config;
+mod csv;
+mod delivery;
+mod derived;
+mod error;
+mod events;
+mod formula;
+mod qc;
+mod socket;
+mod units;
+#[cfg(test)]
 mod tests;
 
 use std::env;
 use actix_web::{App, HttpServer, web, middleware::Logger};
 use dotenv::dotenv;
 use actix_cors::Cors;
-use utoipa::{OpenApi, ToSchema};
+use utoipa::{Modify, OpenApi, ToSchema};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
 use utoipa_swagger_ui::SwaggerUi;
 
 #[derive(OpenApi)]
@@ -21,10 +34,17 @@ use utoipa_swagger_ui::SwaggerUi;
         models::SensorMeasurement,
         models::MeasurementType,
         models::Meteostation,
+        models::MeteostationMetadata,
 
         models::SensorRequest,
         models::SensorResponse,
         models::MeteostationRequest,
+        models::MeteostationQuery,
+        models::StationFeatureCollection,
+        models::StationFeature,
+        models::PointGeometry,
+        models::StationProperties,
+        models::LatestReading,
         models::MeasurementTypeRequest,
         models::SensorMeasurementRequest,
         models::SensorMeasurementsDelete,
@@ -37,6 +57,44 @@ use utoipa_swagger_ui::SwaggerUi;
         models::MeteostationSensorCreateRequest,
         models::MeteostationSensorRemove,
         models::MeasurementRequest,
+        models::MeasurementRejection,
+        models::MeasurementIngestResponse,
+        models::ExportFormat,
+        models::MeasurementExportQuery,
+        models::MeasurementImportQuery,
+        models::MeasurementImportError,
+        models::MeasurementImportResponse,
+        models::MeasurementPage,
+        models::SortOrder,
+        models::MeasurementAggregate,
+        models::MeasurementStreamQuery,
+        models::MeasurementSeries,
+        models::MeasurementReview,
+        models::MeasurementReviewRequest,
+        models::MeasurementReviewEntry,
+        models::SchemaVersion,
+        models::ProblemDetails,
+        models::ApiKey,
+        models::ApiKeyIdentity,
+        models::ApiKeyRequest,
+        models::ApiKeyCreated,
+        auth::Scope,
+        models::AlertRule,
+        models::AlertRuleRequest,
+        models::AlertStatus,
+        models::Alert,
+        alerting::AlertOperator,
+        models::Webhook,
+        models::WebhookRequest,
+        models::WebhookCreated,
+        models::WebhookDelivery,
+        models::DeliveryStatus,
+        delivery::WebhookEvent,
+        qc::QcFlag,
+        models::DerivedType,
+        models::DerivedMeasurement,
+        derived::Quantity,
+        derived::DerivedVariable,
 
         BigDecimal,
     )),
@@ -49,6 +107,7 @@ use utoipa_swagger_ui::SwaggerUi;
         sensors::delete_sensor,
 
         meteostations::get_all_meteostations,
+        meteostations::get_meteostation,
         meteostations::get_sensor_meteostation,
         meteostations::create_meteostation,
         meteostations::update_meteostation,
@@ -67,13 +126,55 @@ use utoipa_swagger_ui::SwaggerUi;
         meteostations_sensor::delete_meteostation_sensor,
 
         measurements::get_measurements,
-        measurements::get_condition_measurements,
+        measurements::get_measurement_aggregates,
+        measurements::get_derived_types,
+        measurements::get_derived_measurements,
+        measurements::stream_measurements,
+        measurements::export_measurements,
+        measurements::import_measurements,
+        measurements::measurements_socket,
         measurements::create_measurements,
+        measurements::create_measurement_reviews,
+        measurements::get_measurement_reviews,
         measurements::remove_measurement,
-    )
+
+        schema::get_schema_version,
+
+        api_keys::get_api_keys,
+        api_keys::get_current_api_key,
+        api_keys::create_api_key,
+        api_keys::delete_api_key,
+
+        alerts::get_alert_rules,
+        alerts::create_alert_rule,
+        alerts::update_alert_rule,
+        alerts::delete_alert_rule,
+        alerts::get_alerts,
+
+        webhooks::get_webhooks,
+        webhooks::create_webhook,
+        webhooks::update_webhook,
+        webhooks::delete_webhook,
+        webhooks::get_webhook_deliveries,
+    ),
+    modifiers(&SecurityAddon),
+    security(("api_key" = []))
 )]
 struct ApiDoc;
 
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_key",
+                SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(auth::API_KEY_HEADER))),
+            );
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(ToSchema)]
 struct BigDecimal(f64);
@@ -85,7 +186,16 @@ async fn main() -> std::io::Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
     let pool = config::get_db_pool().await.expect("Failed to create pool.");
+    config::run_migrations(&pool).await.expect("Failed to apply database migrations.");
+
+    if let Ok(admin_key) = env::var("ADMIN_API_KEY") {
+        auth::bootstrap_admin_key(&pool, &admin_key).await.expect("Failed to register ADMIN_API_KEY.");
+    }
 
+    let ingest_config = config::IngestConfig::from_env();
+    let measurement_events = events::MeasurementEvents::new();
+    actix_web::rt::spawn(delivery::run_worker(pool.clone(), config::WebhookConfig::from_env()));
+    actix_web::rt::spawn(events::run_sequencer(pool.clone(), measurement_events.clone()));
     let openapi = ApiDoc::openapi();
 
     println!("Server is running on http://localhost:8000");
@@ -99,6 +209,17 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(web::Data::new(ingest_config.clone()))
+            .app_data(web::Data::new(measurement_events.clone()))
+            .app_data(
+                web::JsonConfig::default()
+                    .limit(ingest_config.max_payload_bytes)
+                    .error_handler(error::json_error_handler)
+            )
+            .app_data(web::PayloadConfig::default().limit(ingest_config.max_payload_bytes))
+            .app_data(web::QueryConfig::default().error_handler(error::validation_error_handler))
+            .app_data(web::PathConfig::default().error_handler(error::validation_error_handler))
+            .wrap(auth::ApiKeyAuth)
             .wrap(cors)
             .wrap(Logger::default())
             .configure(sensors_routes)
@@ -107,6 +228,10 @@ async fn main() -> std::io::Result<()> {
             .configure(sensor_measurements_routes)
             .configure(meteostations_sensor_routes)
             .configure(measurements_routes)
+            .configure(schema_routes)
+            .configure(api_keys_routes)
+            .configure(alerts_routes)
+            .configure(webhooks_routes)
             .service(SwaggerUi::new("/{_:.*}").url("/api-doc/openapi.json", openapi.clone()))
     })
         .bind(("0.0.0.0", 8000))?
@@ -1,8 +1,13 @@
 use bigdecimal::BigDecimal;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow};
-use chrono::{NaiveDateTime};
+use chrono::{NaiveDate, NaiveDateTime};
 use utoipa::ToSchema;
+use crate::alerting::AlertOperator;
+use crate::auth::Scope;
+use crate::delivery::WebhookEvent;
+use crate::derived::{DerivedVariable, Quantity};
+use crate::qc::QcFlag;
 
 mod datetime_format {
     use chrono::{NaiveDateTime, DateTime};
@@ -80,10 +85,51 @@ pub struct Sensor {
 #[derive(Serialize, Deserialize, FromRow, ToSchema)]
 pub struct Measurement {
     pub sensor_inventory_number: String,
+    /// Station a derived reading was computed for. Derived readings have no sensor, so their
+    /// `sensor_inventory_number` is empty.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub station_id: Option<i32>,
     pub value: BigDecimal,
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    pub calibrated_value: Option<BigDecimal>,
+    /// Units of `calibrated_value` and `corrected_value`, reported when `?units=` is given.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub units: Option<String>,
     #[serde(with = "datetime_format")]
     pub ts: NaiveDateTime,
     pub r#type: Option<i32>,
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub qc_flag: QcFlag,
+    /// Names of the failed QC checks: `range`, `step` or `flatline`.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub qc_checks: Vec<String>,
+    /// Value supplied by a reviewer in place of the calibrated value.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub corrected_value: Option<BigDecimal>,
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub qc_note: Option<String>,
+    /// Name of the API key that last reviewed the reading.
+    #[serde(default, skip_deserializing)]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub reviewed_by: Option<String>,
+    #[serde(default, skip_deserializing, with = "datetime_format::option")]
+    #[schema(read_only)]
+    #[sqlx(skip)]
+    pub reviewed_at: Option<NaiveDateTime>,
 }
 
 #[derive(Serialize, Deserialize, FromRow, ToSchema)]
@@ -104,19 +150,74 @@ pub struct SensorMeasurement {
     pub measurement_formula: Option<String>,
 }
 
-#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct MeasurementType {
     pub id: i32,
     pub name: String,
+    /// Symbol of a registered unit such as `°C`, `km/h` or `hPa`.
     pub units: String,
+    /// Quantity the type records, read when computing derived variables.
+    pub quantity: Option<Quantity>,
+    /// Readings below this calibrated value are flagged `bad`.
+    pub qc_min: Option<BigDecimal>,
+    /// Readings above this calibrated value are flagged `bad`.
+    pub qc_max: Option<BigDecimal>,
+    /// Changes larger than this from the previous reading are flagged `suspect`.
+    pub qc_max_step: Option<BigDecimal>,
+    /// This many identical consecutive readings are flagged `suspect`.
+    pub qc_flatline_count: Option<i32>,
 }
 
-#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct Meteostation {
     pub id: i32,
     pub name: String,
     pub longitude: BigDecimal,
     pub latitude: BigDecimal,
+    #[serde(flatten)]
+    pub metadata: MeteostationMetadata,
+    /// Great-circle distance from `near`, when the query has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(read_only)]
+    pub distance_km: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeteostationQuery {
+    /// `lat,lon` to sort stations by distance from.
+    pub near: Option<String>,
+    /// Only stations within this distance of `near`.
+    pub radius_km: Option<f64>,
+    /// `minLon,minLat,maxLon,maxLat`; `minLon` above `maxLon` crosses the antimeridian.
+    pub bbox: Option<String>,
+    /// Only stations with (`true`) or without (`false`) a currently installed sensor.
+    pub operating: Option<bool>,
+    pub limit: Option<i64>,
+    /// With `Accept: application/geo+json`, add the latest reading of each measurement type.
+    pub latest: Option<bool>,
+}
+
+/// Reporting metadata of a station; every field is optional.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Default)]
+pub struct MeteostationMetadata {
+    /// Height above mean sea level in metres.
+    #[serde(default)]
+    pub elevation: Option<BigDecimal>,
+    /// IANA timezone name such as `Europe/Moscow`.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Five-digit WMO station index.
+    #[serde(default)]
+    pub wmo_index: Option<String>,
+    /// Four-letter ICAO location indicator.
+    #[serde(default)]
+    pub icao_code: Option<String>,
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub commissioned_on: Option<NaiveDate>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -151,12 +252,25 @@ pub struct MeteostationRequest {
     pub name: String,
     pub longitude: BigDecimal,
     pub latitude: BigDecimal,
+    #[serde(flatten)]
+    pub metadata: MeteostationMetadata,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct MeasurementTypeRequest {
     pub name: String,
-    pub units: String
+    pub units: String,
+    /// Types of one quantity are averaged when computing derived variables.
+    #[serde(default)]
+    pub quantity: Option<Quantity>,
+    #[serde(default)]
+    pub qc_min: Option<BigDecimal>,
+    #[serde(default)]
+    pub qc_max: Option<BigDecimal>,
+    #[serde(default)]
+    pub qc_max_step: Option<BigDecimal>,
+    #[serde(default)]
+    pub qc_flatline_count: Option<i32>,
 }
 
 #[derive(Serialize, Deserialize, ToSchema)]
@@ -192,9 +306,64 @@ pub struct MeteostationResponse {
     pub station_name: String,
     pub station_longitude: BigDecimal,
     pub station_latitude: BigDecimal,
+    #[serde(flatten)]
+    pub metadata: MeteostationMetadata,
     pub sensors: Vec<MeteostationSensorResponse>,
 }
 
+/// GeoJSON FeatureCollection of stations (RFC 7946).
+#[derive(Serialize, ToSchema)]
+pub struct StationFeatureCollection {
+    /// Always `FeatureCollection`.
+    pub r#type: String,
+    pub features: Vec<StationFeature>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StationFeature {
+    /// Always `Feature`.
+    pub r#type: String,
+    /// Station ID.
+    pub id: i32,
+    pub geometry: PointGeometry,
+    pub properties: StationProperties,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PointGeometry {
+    /// Always `Point`.
+    pub r#type: String,
+    /// `[longitude, latitude]`.
+    pub coordinates: [f64; 2],
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StationProperties {
+    pub name: String,
+    #[serde(flatten)]
+    pub metadata: MeteostationMetadata,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distance_km: Option<f64>,
+    /// Sensors currently installed at the station.
+    pub sensors: Vec<MeteostationSensorResponse>,
+    /// Latest reading of each measurement type, when requested with `latest=true`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latest: Option<Vec<LatestReading>>,
+}
+
+/// Newest reading of one measurement type at a station, corrected or calibrated when available.
+#[derive(Serialize, ToSchema)]
+pub struct LatestReading {
+    pub type_id: i32,
+    pub type_name: String,
+    pub units: String,
+    pub value: BigDecimal,
+    #[serde(with = "datetime_format")]
+    pub ts: NaiveDateTime,
+    pub sensor_inventory_number: String,
+    pub qc_flag: QcFlag,
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct MeteostationSensorCreate {
     pub station_id: i32,
@@ -219,8 +388,445 @@ pub struct MeasurementRequest {
     pub measurements: Vec<Measurement>
 }
 
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementRejection {
+    pub index: usize,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementIngestResponse {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub rejections: Vec<MeasurementRejection>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    /// Arrow IPC stream.
+    Arrow,
+    Parquet,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Options of a CSV import. Columns are matched by their header.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementImportQuery {
+    /// Single-character field delimiter, `,` by default; `tab` for tab-separated files.
+    pub delimiter: Option<String>,
+    /// chrono `strftime` format of the timestamps, RFC 3339 by default. Timestamps without an
+    /// offset are taken as UTC.
+    pub timestamp_format: Option<String>,
+    /// Defaults to `sensor_inventory_number`.
+    pub inventory_number_column: Option<String>,
+    /// Column of measurement type IDs, `type_id` by default.
+    pub type_column: Option<String>,
+    /// Defaults to `ts`.
+    pub ts_column: Option<String>,
+    /// Defaults to `value`.
+    pub value_column: Option<String>,
+    /// Validate every line without storing anything.
+    pub dry_run: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementImportError {
+    /// Line of the file the record starts on, counting the header as line 1.
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementImportResponse {
+    pub dry_run: bool,
+    /// Records read, excluding the header and blank lines.
+    pub rows: usize,
+    /// Readings stored, or that would be stored in a dry run.
+    pub accepted: usize,
+    pub rejected: usize,
+    pub errors: Vec<MeasurementImportError>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementPage {
+    pub measurements: Vec<Measurement>,
+    pub next_cursor: Option<String>,
+    /// Types on this page that `?units=` asked to convert but whose stored units are not in the
+    /// unit registry; their values are reported as stored.
+    #[serde(default)]
+    pub unconverted_types: Vec<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Which values readings report. `corrected` replaces `calibrated_value` with the reviewer's
+/// correction where there is one. Aggregates follow the same values: calibrated for `raw`,
+/// corrected else calibrated for `corrected`, falling back to the recorded value.
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MeasurementSeries {
+    #[default]
+    Raw,
+    Corrected,
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct MeasurementQuery {
     pub meteostation: Option<i32>,
     pub sensor: Option<i32>,
-}
\ No newline at end of file
+    pub inventory_number: Option<String>,
+    pub r#type: Option<i32>,
+    #[serde(default, with = "datetime_format::option")]
+    pub from: Option<NaiveDateTime>,
+    #[serde(default, with = "datetime_format::option")]
+    pub to: Option<NaiveDateTime>,
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    /// Defaults to `asc`.
+    pub order: Option<SortOrder>,
+    pub qc: Option<QcFlag>,
+    #[serde(default)]
+    pub series: MeasurementSeries,
+    /// Target units: `metric`, `imperial`, `type_id:unit` pairs, or a mix, comma-separated.
+    pub units: Option<String>,
+}
+
+/// Review of the readings of one sensor and type at `ts`. Replaces the flag, correction and note
+/// of an earlier review; the recorded value is kept.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementReview {
+    pub sensor_inventory_number: String,
+    pub r#type: i32,
+    #[serde(with = "datetime_format")]
+    pub ts: NaiveDateTime,
+    pub qc_flag: QcFlag,
+    /// Corrected value in calibrated units, or `null` to keep the calibrated value.
+    #[serde(default)]
+    pub corrected_value: Option<BigDecimal>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementReviewRequest {
+    pub reviews: Vec<MeasurementReview>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementReviewQuery {
+    pub inventory_number: Option<String>,
+    pub r#type: Option<i32>,
+    #[serde(default, with = "datetime_format::option")]
+    pub from: Option<NaiveDateTime>,
+    #[serde(default, with = "datetime_format::option")]
+    pub to: Option<NaiveDateTime>,
+    pub limit: Option<i64>,
+}
+
+/// One entry of the review history of a reading.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementReviewEntry {
+    pub id: i64,
+    pub sensor_inventory_number: String,
+    pub r#type: Option<i32>,
+    #[serde(with = "datetime_format")]
+    pub ts: NaiveDateTime,
+    pub value: BigDecimal,
+    pub previous_qc_flag: QcFlag,
+    pub previous_corrected_value: Option<BigDecimal>,
+    pub qc_flag: QcFlag,
+    pub corrected_value: Option<BigDecimal>,
+    pub note: Option<String>,
+    pub reviewed_by: String,
+    #[serde(with = "datetime_format")]
+    pub reviewed_at: NaiveDateTime,
+}
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementStreamQuery {
+    pub meteostation: Option<i32>,
+    pub sensor: Option<i32>,
+    pub r#type: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct SchemaVersion {
+    pub applied: Option<i64>,
+    pub expected: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct MeasurementAggregateQuery {
+    pub interval: String,
+}
+
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct MeasurementAggregate {
+    #[serde(with = "datetime_format")]
+    pub bucket: NaiveDateTime,
+    pub sensor_inventory_number: String,
+    pub r#type: Option<i32>,
+    pub count: i64,
+    pub min: BigDecimal,
+    pub max: BigDecimal,
+    pub avg: BigDecimal,
+    pub sum: BigDecimal,
+    pub first: BigDecimal,
+    pub last: BigDecimal,
+    /// Units of the values, reported when `?units=` is given.
+    #[sqlx(skip)]
+    pub units: Option<String>,
+}
+
+/// Virtual measurement type computed from co-located readings.
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DerivedType {
+    /// Negative, so it never collides with a stored measurement type.
+    pub id: i32,
+    pub name: DerivedVariable,
+    pub units: String,
+    /// Quantities read from the station; air temperature is always required.
+    pub inputs: Vec<Quantity>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DerivedMeasurementQuery {
+    pub meteostation: Option<i32>,
+    /// Derived type ID; every derived variable when omitted.
+    pub r#type: Option<i32>,
+    #[serde(default, with = "datetime_format::option")]
+    pub from: Option<NaiveDateTime>,
+    #[serde(default, with = "datetime_format::option")]
+    pub to: Option<NaiveDateTime>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DerivedMeasurement {
+    pub station_id: i32,
+    #[serde(with = "datetime_format")]
+    pub ts: NaiveDateTime,
+    pub r#type: i32,
+    pub value: BigDecimal,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ProblemDetails {
+    pub r#type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: String,
+    /// Index of the failed item in a batch request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub station_id: Option<i32>,
+    #[serde(with = "datetime_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "datetime_format::option")]
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyIdentity {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub station_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Binds the key to a station, making it a device token that can only ingest readings
+    /// for sensors currently assigned to that station.
+    #[serde(default)]
+    pub station_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct ApiKeyCreated {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub station_id: Option<i32>,
+    /// Plain-text key, shown only once.
+    pub key: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AlertRule {
+    pub id: i32,
+    pub name: String,
+    /// Restricts the rule to one station, any station when empty.
+    pub station_id: Option<i32>,
+    /// Restricts the rule to one sensor, any sensor when empty.
+    pub sensor_id: Option<i32>,
+    pub type_id: i32,
+    pub operator: AlertOperator,
+    pub threshold: BigDecimal,
+    pub hysteresis: BigDecimal,
+    pub min_duration_seconds: i32,
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AlertRuleRequest {
+    pub name: String,
+    #[serde(default)]
+    pub station_id: Option<i32>,
+    #[serde(default)]
+    pub sensor_id: Option<i32>,
+    pub type_id: i32,
+    pub operator: AlertOperator,
+    pub threshold: BigDecimal,
+    /// Defaults to 0.
+    #[serde(default)]
+    pub hysteresis: Option<BigDecimal>,
+    /// Defaults to 0.
+    #[serde(default)]
+    pub min_duration_seconds: Option<i32>,
+    /// Defaults to `true`.
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Open,
+    Closed,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AlertQuery {
+    pub status: Option<AlertStatus>,
+    pub rule: Option<i32>,
+    pub meteostation: Option<i32>,
+    pub inventory_number: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, FromRow, ToSchema)]
+pub struct Alert {
+    pub id: i32,
+    pub rule_id: i32,
+    pub station_id: i32,
+    pub sensor_inventory_number: String,
+    #[serde(with = "datetime_format")]
+    pub opened_ts: NaiveDateTime,
+    pub opened_value: BigDecimal,
+    #[serde(with = "datetime_format::option")]
+    pub closed_ts: Option<NaiveDateTime>,
+    pub closed_value: Option<BigDecimal>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    pub event_types: Vec<WebhookEvent>,
+    pub enabled: bool,
+    #[serde(with = "datetime_format")]
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WebhookRequest {
+    pub url: String,
+    pub event_types: Vec<WebhookEvent>,
+    /// Key of the `X-Webhook-Signature` HMAC, generated when empty. Kept when updating without one.
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Defaults to `true`.
+    #[serde(default)]
+    pub enabled: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WebhookCreated {
+    pub id: i32,
+    pub url: String,
+    pub event_types: Vec<WebhookEvent>,
+    pub enabled: bool,
+    /// Signing secret, shown only once.
+    pub secret: String,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// Given up after `WEBHOOK_MAX_ATTEMPTS` attempts.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for DeliveryStatus {
+    type Err = crate::error::ApiError;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        match status {
+            "pending" => Ok(DeliveryStatus::Pending),
+            "delivered" => Ok(DeliveryStatus::Delivered),
+            "failed" => Ok(DeliveryStatus::Failed),
+            _ => Err(crate::error::ApiError::Internal(format!("unknown delivery status {}", status))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WebhookDeliveryQuery {
+    pub status: Option<DeliveryStatus>,
+    /// 100 by default and at most 1000.
+    pub limit: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i32,
+    pub event_type: String,
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    #[serde(with = "datetime_format")]
+    pub next_attempt_at: NaiveDateTime,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    #[serde(with = "datetime_format")]
+    pub created_at: NaiveDateTime,
+    #[serde(with = "datetime_format::option")]
+    pub delivered_at: Option<NaiveDateTime>,
+}
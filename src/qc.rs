@@ -0,0 +1,104 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+pub const RANGE_CHECK: &str = "range";
+pub const STEP_CHECK: &str = "step";
+pub const FLATLINE_CHECK: &str = "flatline";
+
+/// Quality-control verdict of a reading, ordered from best to worst.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum QcFlag {
+    #[default]
+    Good,
+    /// Plausible but unusual: a large step or a stuck sensor.
+    Suspect,
+    /// Physically implausible, outside the range of its measurement type.
+    Bad,
+}
+
+impl QcFlag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QcFlag::Good => "good",
+            QcFlag::Suspect => "suspect",
+            QcFlag::Bad => "bad",
+        }
+    }
+}
+
+impl fmt::Display for QcFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for QcFlag {
+    type Err = ApiError;
+
+    fn from_str(flag: &str) -> Result<Self, Self::Err> {
+        match flag {
+            "good" => Ok(QcFlag::Good),
+            "suspect" => Ok(QcFlag::Suspect),
+            "bad" => Ok(QcFlag::Bad),
+            _ => Err(ApiError::Validation(format!("unknown QC flag {}", flag))),
+        }
+    }
+}
+
+/// QC limits of a measurement type; `None` skips the check.
+#[derive(Default, Clone)]
+pub struct QcLimits {
+    pub min: Option<BigDecimal>,
+    pub max: Option<BigDecimal>,
+    /// Largest plausible change from the previous reading.
+    pub max_step: Option<BigDecimal>,
+    /// Number of identical consecutive readings after which the sensor is considered stuck.
+    pub flatline_count: Option<i32>,
+}
+
+impl QcLimits {
+    /// Number of previous readings `check` needs to see.
+    pub fn history_len(&self) -> usize {
+        let flatline = self.flatline_count.map_or(0, |count| count.max(1) as usize - 1);
+        let step = usize::from(self.max_step.is_some());
+        flatline.max(step)
+    }
+
+    /// Flags `value` given the previous readings of the same sensor and type, oldest first.
+    /// Returns the flag and the names of the failed checks.
+    pub fn check(&self, history: &[BigDecimal], value: &BigDecimal) -> (QcFlag, Vec<&'static str>) {
+        let mut flag = QcFlag::Good;
+        let mut checks = Vec::new();
+
+        let below = self.min.as_ref().is_some_and(|min| value < min);
+        let above = self.max.as_ref().is_some_and(|max| value > max);
+        if below || above {
+            flag = QcFlag::Bad;
+            checks.push(RANGE_CHECK);
+        }
+
+        if let (Some(max_step), Some(previous)) = (&self.max_step, history.last()) {
+            if (value - previous).abs() > *max_step {
+                flag = flag.max(QcFlag::Suspect);
+                checks.push(STEP_CHECK);
+            }
+        }
+
+        if let Some(count) = self.flatline_count {
+            let previous = count.max(1) as usize - 1;
+            if history.len() >= previous && history[history.len() - previous..].iter().all(|v| v == value) {
+                flag = flag.max(QcFlag::Suspect);
+                checks.push(FLATLINE_CHECK);
+            }
+        }
+
+        (flag, checks)
+    }
+}
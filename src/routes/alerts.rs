@@ -0,0 +1,116 @@
+use actix_web::{
+    web, HttpResponse, Responder, ResponseError,
+    get, post, put, delete
+};
+use sqlx::PgPool;
+
+use crate::handlers::alerts::*;
+use crate::models::{AlertQuery, AlertRuleRequest};
+
+#[utoipa::path(
+    get,
+    path = "/api/alert_rules",
+    responses(
+        (status = 200, description = "Get all alert rules", body = [AlertRule])
+    )
+)]
+#[get("/api/alert_rules")]
+pub async fn get_alert_rules(pool: web::Data<PgPool>) -> impl Responder {
+    match fetch_alert_rules(pool.get_ref()).await {
+        Ok(rules) => HttpResponse::Ok().json(rules),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/alert_rules",
+    request_body = AlertRuleRequest,
+    responses(
+        (status = 201, description = "Create alert rule", body = AlertRule),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Station, sensor or measurement type not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[post("/api/alert_rules")]
+pub async fn create_alert_rule(pool: web::Data<PgPool>, item: web::Json<AlertRuleRequest>) -> impl Responder {
+    match insert_alert_rule(pool.get_ref(), &item.into_inner()).await {
+        Ok(rule) => HttpResponse::Created().json(rule),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/alert_rules/{id}",
+    params(
+        ("id" = i32, description = "Alert rule ID")
+    ),
+    request_body = AlertRuleRequest,
+    responses(
+        (status = 200, description = "Replace alert rule", body = AlertRule),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Alert rule not found", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Station, sensor or measurement type not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[put("/api/alert_rules/{id}")]
+pub async fn update_alert_rule(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    item: web::Json<AlertRuleRequest>,
+) -> impl Responder {
+    match update_one_alert_rule(pool.get_ref(), path.into_inner(), &item.into_inner()).await {
+        Ok(rule) => HttpResponse::Ok().json(rule),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/alert_rules/{id}",
+    params(
+        ("id" = i32, description = "Alert rule ID")
+    ),
+    responses(
+        (status = 200, description = "Delete alert rule together with its alert history"),
+        (status = 404, description = "Alert rule not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[delete("/api/alert_rules/{id}")]
+pub async fn delete_alert_rule(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
+    match delete_one_alert_rule(pool.get_ref(), path.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/alerts",
+    params(
+        ("status" = Option<AlertStatus>, Query, description = "`open` or `closed`"),
+        ("rule" = Option<i32>, Query, description = "Alert rule ID"),
+        ("meteostation" = Option<i32>, Query, description = "Meteostation ID"),
+        ("inventory_number" = Option<String>, Query, description = "Sensor inventory number")
+    ),
+    responses(
+        (status = 200, description = "Get alerts, most recently opened first", body = [Alert]),
+        (status = 400, description = "Invalid filter", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/alerts")]
+pub async fn get_alerts(pool: web::Data<PgPool>, query: web::Query<AlertQuery>) -> impl Responder {
+    match fetch_alerts(pool.get_ref(), &query).await {
+        Ok(alerts) => HttpResponse::Ok().json(alerts),
+        Err(err) => err.error_response(),
+    }
+}
+
+pub fn alerts_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_alert_rules);
+    cfg.service(create_alert_rule);
+    cfg.service(update_alert_rule);
+    cfg.service(delete_alert_rule);
+    cfg.service(get_alerts);
+}
@@ -0,0 +1,89 @@
+use actix_web::{
+    web, HttpResponse, Responder, ResponseError,
+    get, post, delete
+};
+use sqlx::PgPool;
+
+use crate::auth::Identity;
+use crate::handlers::api_keys::*;
+use crate::models::{ApiKeyIdentity, ApiKeyRequest};
+
+#[utoipa::path(
+    get,
+    path = "/api/api_keys",
+    responses(
+        (status = 200, description = "Get all API keys without their secrets", body = [ApiKey]),
+        (status = 403, description = "API key lacks the admin scope", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/api_keys")]
+pub async fn get_api_keys(pool: web::Data<PgPool>) -> impl Responder {
+    match fetch_api_keys(pool.get_ref()).await {
+        Ok(keys) => HttpResponse::Ok().json(keys),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/api_keys/me",
+    responses(
+        (status = 200, description = "Get the API key used for this request", body = ApiKeyIdentity),
+        (status = 401, description = "Missing or invalid API key", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/api_keys/me")]
+pub async fn get_current_api_key(identity: Identity) -> impl Responder {
+    HttpResponse::Ok().json(ApiKeyIdentity {
+        id: identity.key_id,
+        name: identity.name,
+        scopes: identity.scopes,
+        station_id: identity.station_id,
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/api_keys",
+    request_body = ApiKeyRequest,
+    responses(
+        (status = 201, description = "Create API key, the plain-text key is returned only once", body = ApiKeyCreated),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "API key lacks the admin scope", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Station not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[post("/api/api_keys")]
+pub async fn create_api_key(pool: web::Data<PgPool>, item: web::Json<ApiKeyRequest>) -> impl Responder {
+    match insert_api_key(pool.get_ref(), &item.into_inner()).await {
+        Ok(created) => HttpResponse::Created().json(created),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/api_keys/{id}",
+    params(
+        ("id" = i32, description = "API key ID")
+    ),
+    responses(
+        (status = 200, description = "Revoke API key"),
+        (status = 403, description = "API key lacks the admin scope", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Active API key not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[delete("/api/api_keys/{id}")]
+pub async fn delete_api_key(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
+    match revoke_api_key(pool.get_ref(), path.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(err) => err.error_response(),
+    }
+}
+
+pub fn api_keys_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_api_keys);
+    cfg.service(get_current_api_key);
+    cfg.service(create_api_key);
+    cfg.service(delete_api_key);
+}
@@ -1,4 +1,4 @@
-use actix_web::{get, web, HttpResponse, Responder, post, put, delete};
+use actix_web::{get, web, HttpResponse, Responder, ResponseError, post, put, delete};
 use crate::handlers::measurement_type::*;
 use sqlx::PgPool;
 use crate::models::MeasurementTypeRequest;
@@ -14,7 +14,7 @@ responses(
 async fn get_all_measurement_types(pool: web::Data<PgPool>) -> impl Responder {
     match fetch_measurement_types(pool.get_ref()).await {
         Ok(types) => HttpResponse::Ok().json(types),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -23,7 +23,8 @@ async fn get_all_measurement_types(pool: web::Data<PgPool>) -> impl Responder {
     path = "/api/measurement_types",
     request_body = MeasurementTypeRequest,
     responses(
-    (status = 201, description = "Create new measurement type", body = MeasurementType)
+    (status = 201, description = "Create new measurement type", body = MeasurementType),
+    (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[post("/api/measurement_types")]
@@ -33,7 +34,7 @@ async fn create_measurement_type(
 ) -> impl Responder {
     match insert_measurement_type(pool.get_ref(), &mtype.into_inner()).await {
         Ok(response) => HttpResponse::Created().json(response),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -45,7 +46,10 @@ async fn create_measurement_type(
     ),
     request_body = MeasurementTypeRequest,
     responses(
-    (status = 200, description = "Update measurement type", body = MeasurementType)
+    (status = 200, description = "Update measurement type", body = MeasurementType),
+    (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+    (status = 404, description = "Measurement type not found", body = ProblemDetails, content_type = "application/problem+json"),
+    (status = 409, description = "Units changed while the type has measurements", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[put("/api/measurement_types/{id}")]
@@ -56,7 +60,7 @@ async fn update_measurement_type(
 ) -> impl Responder {
     match update_one_measurement_type(pool.get_ref(), path.into_inner(), &mtype.into_inner()).await {
         Ok(response) => HttpResponse::Ok().json(response),
-        Err(_) => HttpResponse::InternalServerError().finish()
+        Err(err) => err.error_response()
     }
 }
 
@@ -68,7 +72,8 @@ async fn update_measurement_type(
     ),
     responses(
         (status = 200, description = "Delete measurement type"),
-        (status = 404, description = "measurement type not found or used in meteostations_sensors")
+        (status = 404, description = "Measurement type not found", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Measurement type is used by sensors_measurements", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[delete("/api/measurement_types/{id}")]
@@ -78,8 +83,7 @@ async fn delete_measurement_type(
 ) -> impl Responder {
     match delete_one_measurement_type(pool.get_ref(), path.into_inner()).await {
         Ok(_) => HttpResponse::Ok().finish(),
-        Err(sqlx::Error::RowNotFound) => HttpResponse::NotFound().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
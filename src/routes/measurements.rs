@@ -1,59 +1,376 @@
 use actix_web::{
-    web, HttpResponse, Responder,
-    get, post, delete
+    web, HttpRequest, HttpResponse, Responder, ResponseError,
+    get, post, delete, rt
 };
 use sqlx::PgPool;
 
+use crate::auth::Identity;
+use crate::config::IngestConfig;
+use crate::error::ApiError;
+use crate::events::{measurement_stream, MeasurementEvents};
+use crate::handlers::derived_measurements::*;
+use crate::handlers::measurement_export::*;
+use crate::handlers::measurement_import::*;
 use crate::handlers::measurements::*;
-use crate::models::{MeasurementQuery, MeasurementRequest};
+use crate::socket;
+use crate::models::{
+    DerivedMeasurementQuery, MeasurementAggregateQuery, MeasurementExportQuery, MeasurementImportQuery, MeasurementQuery, MeasurementRequest, MeasurementReviewQuery, MeasurementReviewRequest,
+    MeasurementStreamQuery,
+};
 
 #[utoipa::path(
     get,
     path = "/api/measurements",
+    params(
+        ("meteostation" = Option<i32>, Query, description = "Meteostation ID"),
+        ("sensor" = Option<i32>, Query, description = "Sensor ID"),
+        ("inventory_number" = Option<String>, Query, description = "Sensor inventory number"),
+        ("type" = Option<i32>, Query, description = "Measurement type ID, or the negative ID of a derived type from `/api/measurements/derived/types`"),
+        ("from" = Option<String>, Query, description = "Include readings at or after this RFC 3339 timestamp"),
+        ("to" = Option<String>, Query, description = "Include readings before this RFC 3339 timestamp"),
+        ("limit" = Option<i64>, Query, description = "Page size, 1000 by default and at most 10000"),
+        ("cursor" = Option<String>, Query, description = "`next_cursor` of the previous page"),
+        ("order" = Option<SortOrder>, Query, description = "Order by timestamp, `asc` or `desc`"),
+        ("qc" = Option<QcFlag>, Query, description = "Only readings with this QC flag: `good`, `suspect` or `bad`"),
+        ("series" = Option<MeasurementSeries>, Query, description = "`raw` (default) or `corrected` to report reviewed corrections as `calibrated_value`"),
+        ("units" = Option<String>, Query, description = "Convert calibrated and corrected values: `metric`, `imperial`, `type_id:unit` pairs such as `3:kn`, or a comma-separated mix. Each reading reports its `units`; types whose stored units cannot be converted are listed in `unconverted_types`")
+    ),
     responses(
-        (status = 200, description = "Get all measurements", body = [Measurement])
+        (status = 200, description = "Get a page of measurements", body = MeasurementPage),
+        (status = 400, description = "Invalid filter or cursor", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[get("/api/measurements")]
-pub async fn get_measurements(pool: web::Data<PgPool>) -> impl Responder {
-    match fetch_all_measurements(pool.get_ref()).await {
-        Ok(measurements) => HttpResponse::Ok().json(measurements),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+pub async fn get_measurements(pool: web::Data<PgPool>, query: web::Query<MeasurementQuery>) -> impl Responder {
+    let cursor = match query.cursor.as_deref().filter(|c| !c.is_empty()).map(MeasurementCursor::decode) {
+        Some(None) => return ApiError::Validation(String::from("invalid cursor")).error_response(),
+        Some(cursor) => cursor,
+        None => None,
+    };
+
+    match fetch_measurements(pool.get_ref(), &query, cursor.as_ref()).await {
+        Ok(page) => HttpResponse::Ok().json(page),
+        Err(err) => err.error_response(),
     }
 }
 
 #[utoipa::path(
     get,
-    path = "/api/measurements",
+    path = "/api/measurements/aggregate",
     params(
+        ("interval" = String, Query, description = "Bucket width: a number followed by `s`, `m`, `h`, `d` or `w`, e.g. `15m` or `1d`"),
         ("meteostation" = Option<i32>, Query, description = "Meteostation ID"),
-        ("sensor" = Option<i32>, Query, description = "Sensor ID")
+        ("sensor" = Option<i32>, Query, description = "Sensor ID"),
+        ("inventory_number" = Option<String>, Query, description = "Sensor inventory number"),
+        ("type" = Option<i32>, Query, description = "Measurement type ID"),
+        ("from" = String, Query, description = "Include readings at or after this RFC 3339 timestamp"),
+        ("to" = String, Query, description = "Include readings before this RFC 3339 timestamp; the range may span at most 10000 buckets"),
+        ("qc" = Option<QcFlag>, Query, description = "Only aggregate readings with this QC flag: `good`, `suspect` or `bad`"),
+        ("series" = Option<MeasurementSeries>, Query, description = "`raw` (default) aggregates calibrated values, `corrected` the corrected or else calibrated values"),
+        ("units" = Option<String>, Query, description = "Convert the aggregates: `metric`, `imperial`, `type_id:unit` pairs such as `3:kn`, or a comma-separated mix. Each aggregate reports its `units`")
     ),
     responses(
-        (status = 200, description = "Get all measurements by condition", body = [Measurement])
+        (status = 200, description = "Get min/max/avg/sum/count/first/last per bucket, sensor and type", body = [MeasurementAggregate]),
+        (status = 400, description = "Invalid filter, interval or range, or a paging parameter", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
-#[get("/api/measurements")]
-pub async fn get_condition_measurements(pool: web::Data<PgPool>, query: web::Query<MeasurementQuery>) -> impl Responder {
-    match fetch_condition_measurements(pool.get_ref(), query).await {
-        Ok(measurements) => HttpResponse::Ok().json(measurements),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+#[get("/api/measurements/aggregate")]
+pub async fn get_measurement_aggregates(
+    pool: web::Data<PgPool>,
+    query: web::Query<MeasurementQuery>,
+    aggregate: web::Query<MeasurementAggregateQuery>,
+) -> impl Responder {
+    let interval_seconds = match parse_interval(&aggregate.interval) {
+        Some(seconds) => seconds,
+        None => return ApiError::Validation(String::from("invalid interval")).error_response(),
+    };
+
+    match fetch_measurement_aggregates(pool.get_ref(), &query, interval_seconds).await {
+        Ok(aggregates) => HttpResponse::Ok().json(aggregates),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/measurements/derived/types",
+    responses(
+        (status = 200, description = "Get the virtual measurement types computed from co-located readings", body = [DerivedType])
+    )
+)]
+#[get("/api/measurements/derived/types")]
+pub async fn get_derived_types() -> impl Responder {
+    HttpResponse::Ok().json(derived_types())
+}
+
+/// Derived meteorological variables
+///
+/// Dew point, relative humidity from dew point, heat index, wind chill and apparent temperature,
+/// computed from the readings each station took at the same timestamp. Measurement types take part
+/// through their `quantity` and are converted to °C, % and m/s; several types or sensors of one
+/// quantity at a station are averaged. Corrected values are used where a reading was reviewed,
+/// and readings flagged `bad` are skipped. Derived types have negative ids; `/api/measurements`
+/// also serves one derived type at a time, paged like stored readings.
+#[utoipa::path(
+    get,
+    path = "/api/measurements/derived",
+    params(
+        ("meteostation" = Option<i32>, Query, description = "Meteostation ID"),
+        ("type" = Option<i32>, Query, description = "Derived type ID from `/api/measurements/derived/types`, every derived variable when omitted"),
+        ("from" = Option<String>, Query, description = "Include readings at or after this RFC 3339 timestamp"),
+        ("to" = Option<String>, Query, description = "Include readings before this RFC 3339 timestamp"),
+        ("limit" = Option<i64>, Query, description = "Station timestamps to read, 1000 by default and at most 10000")
+    ),
+    responses(
+        (status = 200, description = "Get derived readings, oldest first", body = [DerivedMeasurement]),
+        (status = 400, description = "Invalid filter or unknown derived type", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/measurements/derived")]
+pub async fn get_derived_measurements(pool: web::Data<PgPool>, query: web::Query<DerivedMeasurementQuery>) -> impl Responder {
+    match fetch_derived_measurements(pool.get_ref(), &query).await {
+        Ok(derived) => HttpResponse::Ok().json(derived),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/measurements/stream",
+    params(
+        ("meteostation" = Option<i32>, Query, description = "Meteostation ID"),
+        ("sensor" = Option<i32>, Query, description = "Sensor ID"),
+        ("type" = Option<i32>, Query, description = "Measurement type ID"),
+        ("Last-Event-ID" = Option<i64>, Header, description = "Resume after this event, replaying the readings stored since")
+    ),
+    responses(
+        (status = 200, description = "Server-sent events: `measurement` with a `Measurement` for each stored reading, and `heartbeat` every 15 seconds", content_type = "text/event-stream"),
+        (status = 400, description = "Invalid filter or Last-Event-ID", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/measurements/stream")]
+pub async fn stream_measurements(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    events: web::Data<MeasurementEvents>,
+    query: web::Query<MeasurementStreamQuery>,
+) -> impl Responder {
+    let last_event_id = match req.headers().get("Last-Event-ID").map(|value| value.to_str().ok().and_then(|id| id.parse().ok())) {
+        Some(None) => return ApiError::Validation(String::from("invalid Last-Event-ID")).error_response(),
+        Some(id) => id,
+        None => None,
+    };
+
+    match measurement_stream(pool.get_ref().clone(), events.get_ref(), query.into_inner(), last_event_id).await {
+        Ok(stream) => HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header(("Cache-Control", "no-cache"))
+            .insert_header(("X-Accel-Buffering", "no"))
+            .streaming(stream),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/measurements/export",
+    params(
+        ("meteostation" = Option<i32>, Query, description = "Meteostation ID"),
+        ("sensor" = Option<i32>, Query, description = "Sensor ID"),
+        ("inventory_number" = Option<String>, Query, description = "Sensor inventory number"),
+        ("type" = Option<i32>, Query, description = "Measurement type ID"),
+        ("from" = Option<String>, Query, description = "Include readings at or after this RFC 3339 timestamp"),
+        ("to" = Option<String>, Query, description = "Include readings before this RFC 3339 timestamp"),
+        ("order" = Option<SortOrder>, Query, description = "Order by timestamp, `asc` or `desc`"),
+        ("qc" = Option<QcFlag>, Query, description = "Only readings with this QC flag: `good`, `suspect` or `bad`"),
+        ("series" = Option<MeasurementSeries>, Query, description = "`raw` (default) exports calibrated values, `corrected` the corrected or else calibrated values"),
+        ("units" = Option<String>, Query, description = "Convert the values: `metric`, `imperial`, `type_id:unit` pairs such as `3:kn`, or a comma-separated mix"),
+        ("format" = Option<ExportFormat>, Query, description = "`csv` (default), `arrow` for an Arrow IPC stream or `parquet`")
+    ),
+    responses(
+        (status = 200, description = "Every matching reading, streamed, with the columns \
+`station_id,station_name,sensor_name,sensor_inventory_number,type_id,type_name,units,ts,value`. \
+Arrow and Parquet files type `ts` as a UTC microsecond timestamp and `value` as `decimal(38, 6)`.",
+        content(
+            ("text/csv" = String),
+            ("application/vnd.apache.arrow.stream" = String),
+            ("application/vnd.apache.parquet" = String)
+        )),
+        (status = 400, description = "Invalid filter, `limit` or `cursor` given, or a derived type", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/measurements/export")]
+pub async fn export_measurements(
+    pool: web::Data<PgPool>,
+    query: web::Query<MeasurementQuery>,
+    export: web::Query<MeasurementExportQuery>,
+) -> impl Responder {
+    let format = export.format;
+
+    match export_measurements_stream(pool.get_ref().clone(), query.into_inner(), format).await {
+        Ok(stream) => HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", format.file_name())))
+            .streaming(stream),
+        Err(err) => err.error_response(),
     }
 }
 
+/// Ingest and live readings over a WebSocket
+///
+/// JSON text frames. Send `{"type": "measurements", "measurements": [...]}` to store a batch; each one
+/// is answered with `{"type": "ack", "frame", "accepted", "rejected", "rejections"}` or
+/// `{"type": "error", "frame", "problem"}`. Send `{"type": "subscribe", "stations": [...]}` or
+/// `unsubscribe` to choose stations whose new readings arrive as `{"type": "measurement", "id", "measurement"}`.
+#[utoipa::path(
+    get,
+    path = "/api/measurements/ws",
+    responses(
+        (status = 101, description = "Switching to the WebSocket protocol"),
+        (status = 400, description = "Not a WebSocket handshake", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/measurements/ws")]
+pub async fn measurements_socket(
+    req: HttpRequest,
+    body: web::Payload,
+    pool: web::Data<PgPool>,
+    events: web::Data<MeasurementEvents>,
+    config: web::Data<IngestConfig>,
+    identity: Identity,
+) -> impl Responder {
+    let (response, session, messages) = match actix_ws::handle(&req, body) {
+        Ok(handshake) => handshake,
+        Err(err) => return ApiError::Validation(err.to_string()).error_response(),
+    };
+
+    let messages = messages
+        .max_frame_size(config.max_payload_bytes)
+        .aggregate_continuations()
+        .max_continuation_size(config.max_payload_bytes);
+
+    rt::spawn(socket::run(
+        session,
+        messages,
+        pool.get_ref().clone(),
+        events.get_ref().clone(),
+        config.get_ref().clone(),
+        identity,
+    ));
+
+    response
+}
+
 #[utoipa::path(
     post,
     path = "/api/measurements",
     request_body = MeasurementRequest,
     responses(
-        (status = 200, description = "Create measurements")
+        (status = 200, description = "Store valid measurements and report rejected ones", body = MeasurementIngestResponse),
+        (status = 400, description = "Invalid request or batch larger than MEASUREMENTS_MAX_BATCH_SIZE", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[post("/api/measurements")]
-pub async fn create_measurements(pool: web::Data<PgPool>, item: web::Json<MeasurementRequest>) -> impl Responder {
-    match insert_measurements(pool.get_ref(), &item.into_inner()).await {
-        Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish()
+pub async fn create_measurements(
+    pool: web::Data<PgPool>,
+    events: web::Data<MeasurementEvents>,
+    config: web::Data<IngestConfig>,
+    identity: Identity,
+    item: web::Json<MeasurementRequest>,
+) -> impl Responder {
+    match insert_measurements(pool.get_ref(), events.get_ref(), &item.into_inner(), config.max_batch_size, identity.station_id).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(err) => err.error_response()
+    }
+}
+
+/// Import readings from CSV
+///
+/// The body is a CSV file with a header line. Each record is validated like a reading posted to
+/// `/api/measurements`; invalid records are reported by line in `errors` and skipped while the
+/// rest of the file is stored.
+#[utoipa::path(
+    post,
+    path = "/api/measurements/import",
+    params(
+        ("delimiter" = Option<String>, Query, description = "Single-character field delimiter, `,` by default; `tab` for tab-separated files"),
+        ("timestamp_format" = Option<String>, Query, description = "chrono `strftime` format such as `%d.%m.%Y %H:%M`, RFC 3339 by default; timestamps without an offset are UTC"),
+        ("inventory_number_column" = Option<String>, Query, description = "Header of the inventory number column, `sensor_inventory_number` by default"),
+        ("type_column" = Option<String>, Query, description = "Header of the measurement type ID column, `type_id` by default"),
+        ("ts_column" = Option<String>, Query, description = "Header of the timestamp column, `ts` by default"),
+        ("value_column" = Option<String>, Query, description = "Header of the value column, `value` by default"),
+        ("dry_run" = Option<bool>, Query, description = "Validate every line without storing anything")
+    ),
+    request_body(content = String, content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Store the valid readings and report the rejected lines", body = MeasurementImportResponse),
+        (status = 400, description = "Invalid options, header or encoding", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[post("/api/measurements/import")]
+pub async fn import_measurements(
+    pool: web::Data<PgPool>,
+    events: web::Data<MeasurementEvents>,
+    config: web::Data<IngestConfig>,
+    identity: Identity,
+    query: web::Query<MeasurementImportQuery>,
+    body: web::Bytes,
+) -> impl Responder {
+    match import_measurements_csv(
+        pool.get_ref(),
+        events.get_ref(),
+        &body,
+        &query,
+        config.max_batch_size,
+        identity.station_id,
+    ).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/measurements/reviews",
+    request_body = MeasurementReviewRequest,
+    responses(
+        (status = 200, description = "Flag, annotate or correct readings, returning them as reviewed", body = [Measurement]),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "A review matches no reading; nothing was changed", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[post("/api/measurements/reviews")]
+pub async fn create_measurement_reviews(
+    pool: web::Data<PgPool>,
+    identity: Identity,
+    item: web::Json<MeasurementReviewRequest>,
+) -> impl Responder {
+    match review_measurements(pool.get_ref(), &item.reviews, &identity).await {
+        Ok(measurements) => HttpResponse::Ok().json(measurements),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/measurements/reviews",
+    params(
+        ("inventory_number" = Option<String>, Query, description = "Sensor inventory number"),
+        ("type" = Option<i32>, Query, description = "Measurement type ID"),
+        ("from" = Option<String>, Query, description = "Reviews of readings at or after this RFC 3339 timestamp"),
+        ("to" = Option<String>, Query, description = "Reviews of readings before this RFC 3339 timestamp"),
+        ("limit" = Option<i64>, Query, description = "Number of reviews, 100 by default and at most 1000")
+    ),
+    responses(
+        (status = 200, description = "Review history, newest first", body = [MeasurementReviewEntry]),
+        (status = 400, description = "Invalid filter", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/measurements/reviews")]
+pub async fn get_measurement_reviews(pool: web::Data<PgPool>, query: web::Query<MeasurementReviewQuery>) -> impl Responder {
+    match fetch_measurement_reviews(pool.get_ref(), &query).await {
+        Ok(reviews) => HttpResponse::Ok().json(reviews),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -71,14 +388,22 @@ pub async fn create_measurements(pool: web::Data<PgPool>, item: web::Json<Measur
 pub async fn remove_measurement(pool: web::Data<PgPool>, number: web::Path<String>) -> impl Responder {
     match delete_measurement(pool.get_ref(), number.into_inner()).await {
         Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish()
+        Err(err) => err.error_response()
     }
 }
 
 
 pub fn measurements_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_measurements);
-    cfg.service(get_condition_measurements);
+    cfg.service(get_measurement_aggregates);
+    cfg.service(get_derived_types);
+    cfg.service(get_derived_measurements);
+    cfg.service(stream_measurements);
+    cfg.service(export_measurements);
+    cfg.service(measurements_socket);
     cfg.service(create_measurements);
+    cfg.service(import_measurements);
+    cfg.service(create_measurement_reviews);
+    cfg.service(get_measurement_reviews);
     cfg.service(remove_measurement);
 }
\ No newline at end of file
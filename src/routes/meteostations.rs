@@ -1,20 +1,70 @@
-use actix_web::{get, web, HttpResponse, Responder, post, put, delete};
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse, Responder, ResponseError, post, put, delete};
 use crate::handlers::meteostations::*;
 use sqlx::PgPool;
-use crate::models::MeteostationRequest;
+use crate::models::{MeteostationQuery, MeteostationRequest};
 
 #[utoipa::path(
 get,
 path = "/api/meteostations",
+params(
+("near" = Option<String>, Query, description = "`lat,lon`; sorts stations by great-circle distance and reports `distance_km`"),
+("radius_km" = Option<f64>, Query, description = "Only stations within this distance of `near`"),
+("bbox" = Option<String>, Query, description = "`minLon,minLat,maxLon,maxLat`; `minLon` above `maxLon` crosses the antimeridian"),
+("operating" = Option<bool>, Query, description = "Only stations with (`true`) or without (`false`) a currently installed sensor"),
+("limit" = Option<i64>, Query, description = "Maximum number of stations"),
+("latest" = Option<bool>, Query, description = "With `Accept: application/geo+json`, add the latest reading of each measurement type")
+),
 responses(
-(status = 200, description = "Get all meteostations", body = [Meteostation])
+(status = 200, description = "Get meteostations, nearest first with `near`, otherwise by ID. \
+`Accept: application/geo+json` returns a GeoJSON FeatureCollection with the installed sensors of each station.",
+content(
+("application/json" = [Meteostation]),
+("application/geo+json" = StationFeatureCollection)
+)),
+(status = 400, description = "Invalid point, radius or bounding box", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[get("/api/meteostations")]
-async fn get_all_meteostations(pool: web::Data<PgPool>) -> impl Responder {
-    match fetch_meteostations(pool.get_ref()).await {
+async fn get_all_meteostations(
+    req: HttpRequest,
+    pool: web::Data<PgPool>,
+    query: web::Query<MeteostationQuery>,
+) -> impl Responder {
+    let geojson = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(GEOJSON_CONTENT_TYPE));
+
+    if geojson {
+        return match fetch_meteostation_features(pool.get_ref(), &query).await {
+            Ok(features) => HttpResponse::Ok().content_type(GEOJSON_CONTENT_TYPE).json(features),
+            Err(err) => err.error_response(),
+        };
+    }
+
+    match fetch_meteostations(pool.get_ref(), &query).await {
         Ok(meteostations) => HttpResponse::Ok().json(meteostations),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+get,
+path = "/api/meteostations/{id}",
+params(
+("id" = i32, description = "Station ID")
+),
+responses(
+(status = 200, description = "Get meteostation", body = Meteostation),
+(status = 404, description = "Meteostation not found", body = ProblemDetails, content_type = "application/problem+json")
+)
+)]
+#[get("/api/meteostations/{id}")]
+async fn get_meteostation(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
+    match fetch_meteostation(pool.get_ref(), path.into_inner()).await {
+        Ok(meteostation) => HttpResponse::Ok().json(meteostation),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -29,7 +79,7 @@ responses(
 async fn get_sensor_meteostation(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
     match fetch_sensor_meteostation(pool.get_ref(), path.into_inner()).await {
         Ok(meteostation) => HttpResponse::Ok().json(meteostation),
-        Err(_) => HttpResponse::NotFound().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -38,7 +88,8 @@ post,
 path = "/api/meteostations",
 request_body = MeteostationRequest,
 responses(
-(status = 201, description = "Create new meteostation", body = Meteostation)
+(status = 201, description = "Create new meteostation", body = Meteostation),
+(status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[post("/api/meteostations")]
@@ -48,7 +99,7 @@ async fn create_meteostation(
 ) -> impl Responder {
     match insert_meteostation(pool.get_ref(), &meteostation.into_inner()).await {
         Ok(meteostation) => HttpResponse::Created().json(meteostation),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -60,7 +111,9 @@ params(
 ),
 request_body = MeteostationRequest,
 responses(
-(status = 200, description = "Update station", body = Meteostation)
+(status = 200, description = "Update station", body = Meteostation),
+(status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+(status = 404, description = "Meteostation not found", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[put("/api/meteostations/{id}")]
@@ -71,7 +124,7 @@ async fn update_meteostation(
 ) -> impl Responder {
     match update_one_station(pool.get_ref(), path.into_inner(), &station.into_inner()).await {
         Ok(station) => HttpResponse::Ok().json(station),
-        Err(_) => HttpResponse::InternalServerError().finish()
+        Err(err) => err.error_response()
     }
 }
 
@@ -83,7 +136,8 @@ params(
 ),
 responses(
 (status = 200, description = "Delete meteostation"),
-(status = 404, description = "Meteostation not found or has measurements in meteostations_sensors")
+(status = 404, description = "Meteostation not found", body = ProblemDetails, content_type = "application/problem+json"),
+(status = 409, description = "Meteostation has sensors in meteostations_sensors", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[delete("/api/meteostations/{id}")]
@@ -93,13 +147,13 @@ async fn delete_meteostation(
 ) -> impl Responder {
     match delete_one_station(pool.get_ref(), path.into_inner()).await {
         Ok(_) => HttpResponse::Ok().finish(),
-        Err(sqlx::Error::RowNotFound) => HttpResponse::NotFound().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
 pub fn meteostations_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(get_all_meteostations);
+    cfg.service(get_meteostation);
     cfg.service(get_sensor_meteostation);
     cfg.service(create_meteostation);
     cfg.service(update_meteostation);
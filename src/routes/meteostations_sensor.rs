@@ -1,5 +1,5 @@
 use actix_web::{
-    web, HttpResponse, Responder,
+    web, HttpResponse, Responder, ResponseError,
     get, post, put
 };
 use sqlx::PgPool;
@@ -18,7 +18,7 @@ use crate::models::{MeteostationSensorCreateRequest, MeteostationSensorRemove};
 pub async fn get_all_meteostations_sensor(pool: web::Data<PgPool>) -> impl Responder {
     match fetch_meteostation_sensors(pool.get_ref()).await {
         Ok(response) => HttpResponse::Ok().json(response),
-        Err(_) => HttpResponse::InternalServerError().finish()
+        Err(err) => err.error_response()
     }
 }
 
@@ -26,14 +26,16 @@ pub async fn get_all_meteostations_sensor(pool: web::Data<PgPool>) -> impl Respo
     post,
     path = "/api/meteostations_sensors",
     responses(
-        (status = 200, description = "Create binding of sensors to stations")
+        (status = 200, description = "Create binding of sensors to stations"),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "Unknown meteostation or sensor", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[post("/api/meteostations_sensors")]
 pub async fn create_meteostations_sensor(pool: web::Data<PgPool>, item: web::Json<MeteostationSensorCreateRequest>) -> impl Responder {
     match insert_meteostation_sensors(pool.get_ref(), &item.into_inner()).await {
         Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish()
+        Err(err) => err.error_response()
     }
 }
 
@@ -43,14 +45,15 @@ pub async fn create_meteostations_sensor(pool: web::Data<PgPool>, item: web::Jso
     request_body = MeteostationSensorRemove,
     responses(
         (status = 200, description = "Meteostation sensor removed"),
-        (status = 400, description = "Bad request")
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Sensor inventory number not found", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[put("/api/meteostations_sensors/{inventory_number}/removed_ts")]
 pub async fn delete_meteostation_sensor(pool: web::Data<PgPool>, number: web::Path<String>, item: web::Json<MeteostationSensorRemove>) -> impl Responder {
     match remove_meteostation_sensor(pool.get_ref(), number.into_inner(), &item.into_inner()).await {
         Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish()
+        Err(err) => err.error_response()
     }
 }
 
@@ -4,10 +4,18 @@ pub mod sensors;
 pub mod sensors_measurements;
 pub mod meteostations_sensor;
 pub mod measurements;
+pub mod schema;
+pub mod api_keys;
+pub mod alerts;
+pub mod webhooks;
 
 pub use measurement_type::*;
 pub use meteostations::*;
 pub use sensors::*;
 pub use sensors_measurements::*;
 pub use meteostations_sensor::*;
-pub use measurements::*;
\ No newline at end of file
+pub use measurements::*;
+pub use schema::*;
+pub use api_keys::*;
+pub use alerts::*;
+pub use webhooks::*;
\ No newline at end of file
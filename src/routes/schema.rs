@@ -0,0 +1,28 @@
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use sqlx::PgPool;
+
+use crate::config::{applied_schema_version, expected_schema_version};
+use crate::error::ApiError;
+use crate::models::SchemaVersion;
+
+#[utoipa::path(
+    get,
+    path = "/api/schema_version",
+    responses(
+        (status = 200, description = "Get applied and expected database schema versions", body = SchemaVersion)
+    )
+)]
+#[get("/api/schema_version")]
+pub async fn get_schema_version(pool: web::Data<PgPool>) -> impl Responder {
+    match applied_schema_version(pool.get_ref()).await {
+        Ok(applied) => HttpResponse::Ok().json(SchemaVersion {
+            applied,
+            expected: expected_schema_version(),
+        }),
+        Err(err) => ApiError::from(err).error_response(),
+    }
+}
+
+pub fn schema_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_schema_version);
+}
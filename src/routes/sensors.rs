@@ -1,5 +1,5 @@
 use actix_web::{
-    web, HttpResponse, Responder,
+    web, HttpResponse, Responder, ResponseError,
     get, post, put, delete
 };
 use sqlx::PgPool;
@@ -18,7 +18,7 @@ responses(
 async fn get_sensors(pool: web::Data<PgPool>) -> impl Responder {
     match fetch_sensors(pool.get_ref()).await {
         Ok(sensors) => HttpResponse::Ok().json(sensors),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -29,14 +29,15 @@ params(
 ("id" = i32, description = "Sensor ID")
 ),
 responses(
-(status = 200, description = "Get sensor by ID", body = SensorResponse)
+(status = 200, description = "Get sensor by ID", body = SensorResponse),
+(status = 404, description = "Sensor not found", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[get("/api/sensors/{id}")]
 async fn get_sensor(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
     match fetch_sensor(pool.get_ref(), path.into_inner()).await {
         Ok(sensor) => HttpResponse::Ok().json(sensor),
-        Err(_) => HttpResponse::NotFound().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -54,7 +55,7 @@ responses(
 async fn get_sensor_types(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
     match fetch_sensor_types(pool.get_ref(), path.into_inner()).await {
         Ok(sensor_types) => HttpResponse::Ok().json(sensor_types),
-        Err(_) => HttpResponse::NotFound().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -63,14 +64,16 @@ post,
 path = "/api/sensors",
 request_body = SensorRequest,
 responses(
-(status = 201, description = "Create new sensor", body = SensorResponse)
+(status = 201, description = "Create new sensor", body = SensorResponse),
+(status = 400, description = "Invalid request or measurement formula", body = ProblemDetails, content_type = "application/problem+json"),
+(status = 409, description = "Unknown or duplicate measurement type", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[post("/api/sensors")]
 async fn create_sensor(pool: web::Data<PgPool>, sensor: web::Json<SensorRequest>) -> impl Responder {
     match insert_sensor(pool.get_ref(), &sensor.into_inner()).await {
         Ok(new_sensor) => HttpResponse::Created().json(new_sensor),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -82,7 +85,10 @@ params(
 ),
 request_body = SensorRequest,
 responses(
-(status = 200, description = "Update sensor", body = SensorResponse)
+(status = 200, description = "Update sensor", body = SensorResponse),
+(status = 400, description = "Invalid request or measurement formula", body = ProblemDetails, content_type = "application/problem+json"),
+(status = 404, description = "Sensor not found", body = ProblemDetails, content_type = "application/problem+json"),
+(status = 409, description = "Unknown or duplicate measurement type", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[put("/api/sensors/{id}")]
@@ -93,7 +99,7 @@ async fn update_sensor(
 ) -> impl Responder {
     match update_one_sensor(pool.get_ref(), path.into_inner(), &sensor.into_inner()).await {
         Ok(updated_sensor) => HttpResponse::Ok().json(updated_sensor),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -105,15 +111,15 @@ params(
 ),
 responses(
 (status = 200, description = "Delete sensor"),
-(status = 404, description = "Sensor not found or has measurements in meteostations_sensors")
+(status = 404, description = "Sensor not found", body = ProblemDetails, content_type = "application/problem+json"),
+(status = 409, description = "Sensor is assigned in meteostations_sensors", body = ProblemDetails, content_type = "application/problem+json")
 )
 )]
 #[delete("/api/sensors/{id}")]
 async fn delete_sensor(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
     match delete_one_sensor(pool.get_ref(), path.into_inner()).await {
         Ok(_) => HttpResponse::Ok().finish(),
-        Err(sqlx::Error::RowNotFound) => HttpResponse::NotFound().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -1,4 +1,4 @@
-use actix_web::{web, HttpResponse, Responder, post, delete};
+use actix_web::{web, HttpResponse, Responder, ResponseError, post, delete};
 use crate::handlers::sensors_measurements::*;
 use sqlx::PgPool;
 use crate::models::{SensorMeasurementRequest, SensorMeasurementsDelete};
@@ -11,7 +11,9 @@ use crate::models::{SensorMeasurementRequest, SensorMeasurementsDelete};
     ),
     request_body = SensorMeasurementRequest,
     responses(
-    (status = 201, description = "Create new sensor measurements")
+    (status = 201, description = "Create new sensor measurements"),
+    (status = 400, description = "Invalid request or measurement formula", body = ProblemDetails, content_type = "application/problem+json"),
+    (status = 409, description = "Unknown sensor or measurement type, or type already assigned", body = ProblemDetails, content_type = "application/problem+json")
     )
 )]
 #[post("/api/sensor_measurements/{sensor_id}")]
@@ -22,7 +24,7 @@ async fn create_sensor_measurements(
 ) -> impl Responder {
     match insert_sensor_measurements(pool.get_ref(), id.into_inner(), &sensor_measurement.into_inner()).await {
         Ok(_) => HttpResponse::Created().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -45,7 +47,7 @@ async fn delete_sensor_measurements(
 ) -> impl Responder {
     match delete_many_sensor_measurements(pool.get_ref(), id.into_inner(), &path.into_inner()).await {
         Ok(_) => HttpResponse::Ok().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+        Err(err) => err.error_response(),
     }
 }
 
@@ -0,0 +1,124 @@
+use actix_web::{
+    web, HttpResponse, Responder, ResponseError,
+    get, post, put, delete
+};
+use sqlx::PgPool;
+
+use crate::handlers::webhooks::*;
+use crate::models::{WebhookDeliveryQuery, WebhookRequest};
+
+#[utoipa::path(
+    get,
+    path = "/api/webhooks",
+    responses(
+        (status = 200, description = "Get all webhooks without their secrets", body = [Webhook]),
+        (status = 403, description = "API key lacks the admin scope", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/webhooks")]
+pub async fn get_webhooks(pool: web::Data<PgPool>) -> impl Responder {
+    match fetch_webhooks(pool.get_ref()).await {
+        Ok(webhooks) => HttpResponse::Ok().json(webhooks),
+        Err(err) => err.error_response(),
+    }
+}
+
+/// Register a webhook
+///
+/// Each delivery is a JSON POST with `X-Webhook-Event`, `X-Webhook-Delivery`, `X-Webhook-Timestamp`
+/// and `X-Webhook-Signature: sha256=<hex>`, the HMAC-SHA256 of `"{timestamp}.{body}"` keyed with the
+/// secret. Non-2xx responses are retried with exponential backoff up to `WEBHOOK_MAX_ATTEMPTS` times.
+#[utoipa::path(
+    post,
+    path = "/api/webhooks",
+    request_body = WebhookRequest,
+    responses(
+        (status = 201, description = "Create webhook, the secret is returned only once", body = WebhookCreated),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "API key lacks the admin scope", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[post("/api/webhooks")]
+pub async fn create_webhook(pool: web::Data<PgPool>, item: web::Json<WebhookRequest>) -> impl Responder {
+    match insert_webhook(pool.get_ref(), &item.into_inner()).await {
+        Ok(created) => HttpResponse::Created().json(created),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/webhooks/{id}",
+    params(
+        ("id" = i32, description = "Webhook ID")
+    ),
+    request_body = WebhookRequest,
+    responses(
+        (status = 200, description = "Replace webhook, keeping the secret unless a new one is given", body = Webhook),
+        (status = 400, description = "Invalid request", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[put("/api/webhooks/{id}")]
+pub async fn update_webhook(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    item: web::Json<WebhookRequest>,
+) -> impl Responder {
+    match update_one_webhook(pool.get_ref(), path.into_inner(), &item.into_inner()).await {
+        Ok(webhook) => HttpResponse::Ok().json(webhook),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/webhooks/{id}",
+    params(
+        ("id" = i32, description = "Webhook ID")
+    ),
+    responses(
+        (status = 200, description = "Delete webhook together with its pending deliveries and log"),
+        (status = 404, description = "Webhook not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[delete("/api/webhooks/{id}")]
+pub async fn delete_webhook(pool: web::Data<PgPool>, path: web::Path<i32>) -> impl Responder {
+    match delete_one_webhook(pool.get_ref(), path.into_inner()).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(err) => err.error_response(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/{id}/deliveries",
+    params(
+        ("id" = i32, description = "Webhook ID"),
+        ("status" = Option<DeliveryStatus>, Query, description = "`pending`, `delivered` or `failed`"),
+        ("limit" = Option<i64>, Query, description = "Number of deliveries, 100 by default and at most 1000")
+    ),
+    responses(
+        (status = 200, description = "Get the delivery log, newest first", body = [WebhookDelivery]),
+        (status = 404, description = "Webhook not found", body = ProblemDetails, content_type = "application/problem+json")
+    )
+)]
+#[get("/api/webhooks/{id}/deliveries")]
+pub async fn get_webhook_deliveries(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    query: web::Query<WebhookDeliveryQuery>,
+) -> impl Responder {
+    match fetch_webhook_deliveries(pool.get_ref(), path.into_inner(), &query).await {
+        Ok(deliveries) => HttpResponse::Ok().json(deliveries),
+        Err(err) => err.error_response(),
+    }
+}
+
+pub fn webhooks_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_webhooks);
+    cfg.service(create_webhook);
+    cfg.service(update_webhook);
+    cfg.service(delete_webhook);
+    cfg.service(get_webhook_deliveries);
+}
@@ -0,0 +1,216 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason, Session};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+use crate::auth::{Identity, Scope};
+use crate::config::IngestConfig;
+use crate::error::ApiError;
+use crate::events::{MeasurementEvent, MeasurementEvents, HEARTBEAT_INTERVAL};
+use crate::handlers::measurements::insert_measurements;
+use crate::models::{Measurement, MeasurementIngestResponse, MeasurementRequest, ProblemDetails};
+
+/// Sockets that send nothing, not even a pong, for this long are closed.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(2 * HEARTBEAT_INTERVAL.as_secs());
+
+/// Text frame sent by the client.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    /// A `MeasurementRequest` batch to store.
+    Measurements(MeasurementRequest),
+    /// Adds stations whose new readings are pushed to this socket.
+    Subscribe { stations: Vec<i32> },
+    Unsubscribe { stations: Vec<i32> },
+}
+
+/// Text frame sent to the client.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ServerMessage<'a> {
+    /// Result of the `frame`-th measurements frame of this socket, counting from 1.
+    Ack {
+        frame: u64,
+        #[serde(flatten)]
+        response: MeasurementIngestResponse,
+    },
+    Error {
+        frame: Option<u64>,
+        problem: ProblemDetails,
+    },
+    Subscribed { stations: Vec<i32> },
+    Measurement { id: i64, measurement: &'a Measurement },
+    /// Live readings were dropped because the client did not keep up.
+    Lagged { skipped: u64 },
+}
+
+struct Connection {
+    session: Session,
+    pool: PgPool,
+    events: MeasurementEvents,
+    config: IngestConfig,
+    identity: Identity,
+    frames: u64,
+    stations: HashSet<i32>,
+    receiver: Option<broadcast::Receiver<Arc<MeasurementEvent>>>,
+}
+
+impl Connection {
+    async fn send(&mut self, message: &ServerMessage<'_>) -> Result<(), actix_ws::Closed> {
+        let text = serde_json::to_string(message).unwrap_or_default();
+        self.session.text(text).await
+    }
+
+    async fn send_error(&mut self, frame: Option<u64>, err: ApiError) -> Result<(), actix_ws::Closed> {
+        self.send(&ServerMessage::Error { frame, problem: err.problem() }).await
+    }
+
+    async fn handle_text(&mut self, text: &str) -> Result<(), actix_ws::Closed> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(err) => return self.send_error(None, ApiError::Validation(err.to_string())).await,
+        };
+
+        match message {
+            ClientMessage::Measurements(request) => {
+                self.frames += 1;
+                let frame = self.frames;
+
+                if !self.identity.allows(Scope::Write) {
+                    let err = ApiError::Forbidden(String::from("API key lacks the write scope"));
+                    return self.send_error(Some(frame), err).await;
+                }
+
+                let result = insert_measurements(
+                    &self.pool,
+                    &self.events,
+                    &request,
+                    self.config.max_batch_size,
+                    self.identity.station_id,
+                )
+                    .await;
+
+                match result {
+                    Ok(response) => self.send(&ServerMessage::Ack { frame, response }).await,
+                    Err(err) => self.send_error(Some(frame), err).await,
+                }
+            }
+            ClientMessage::Subscribe { stations } => {
+                if !self.identity.allows(Scope::Read) {
+                    let err = ApiError::Forbidden(String::from("API key lacks the read scope"));
+                    return self.send_error(None, err).await;
+                }
+
+                self.stations.extend(stations);
+                if self.receiver.is_none() {
+                    self.receiver = Some(self.events.subscribe());
+                }
+                self.send_subscribed().await
+            }
+            ClientMessage::Unsubscribe { stations } => {
+                for station_id in stations {
+                    self.stations.remove(&station_id);
+                }
+                if self.stations.is_empty() {
+                    self.receiver = None;
+                }
+                self.send_subscribed().await
+            }
+        }
+    }
+
+    async fn send_subscribed(&mut self) -> Result<(), actix_ws::Closed> {
+        let mut stations: Vec<i32> = self.stations.iter().copied().collect();
+        stations.sort_unstable();
+        self.send(&ServerMessage::Subscribed { stations }).await
+    }
+
+    async fn handle_event(&mut self, received: Result<Arc<MeasurementEvent>, RecvError>) -> Result<(), actix_ws::Closed> {
+        match received {
+            Ok(event) if self.stations.contains(&event.station_id) => {
+                self.send(&ServerMessage::Measurement { id: event.id, measurement: &event.measurement }).await
+            }
+            Ok(_) => Ok(()),
+            Err(RecvError::Lagged(skipped)) => self.send(&ServerMessage::Lagged { skipped }).await,
+            Err(RecvError::Closed) => {
+                self.receiver = None;
+                Ok(())
+            }
+        }
+    }
+}
+
+async fn next_event(receiver: &mut Option<broadcast::Receiver<Arc<MeasurementEvent>>>) -> Result<Arc<MeasurementEvent>, RecvError> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Serves one socket: stores `measurements` frames, acknowledging each, and pushes live readings
+/// of the subscribed stations. Runs until either side closes the connection, or the client stops
+/// answering heartbeat pings.
+pub async fn run(
+    session: Session,
+    mut messages: AggregatedMessageStream,
+    pool: PgPool,
+    events: MeasurementEvents,
+    config: IngestConfig,
+    identity: Identity,
+) {
+    let mut connection = Connection {
+        session,
+        pool,
+        events,
+        config,
+        identity,
+        frames: 0,
+        stations: HashSet::new(),
+        receiver: None,
+    };
+
+    let mut heartbeat = interval(HEARTBEAT_INTERVAL);
+    heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    heartbeat.reset();
+    let mut last_seen = Instant::now();
+
+    let reason = loop {
+        let result = tokio::select! {
+            message = messages.recv() => {
+                let result = match message {
+                    Some(Ok(AggregatedMessage::Text(text))) => connection.handle_text(&text).await,
+                    Some(Ok(AggregatedMessage::Binary(_))) => {
+                        let err = ApiError::Validation(String::from("binary frames are not supported, send JSON text frames"));
+                        connection.send_error(None, err).await
+                    }
+                    Some(Ok(AggregatedMessage::Ping(bytes))) => connection.session.pong(&bytes).await,
+                    Some(Ok(AggregatedMessage::Pong(_))) => Ok(()),
+                    Some(Ok(AggregatedMessage::Close(reason))) => break reason,
+                    Some(Err(_)) | None => break None,
+                };
+                // Counted from after the frame is handled, so storing a large batch is not
+                // mistaken for a silent client.
+                last_seen = Instant::now();
+                result
+            }
+            received = next_event(&mut connection.receiver) => connection.handle_event(received).await,
+            _ = heartbeat.tick() => {
+                if last_seen.elapsed() > CLIENT_TIMEOUT {
+                    break Some(CloseReason::from((CloseCode::Away, "heartbeat timeout")));
+                }
+                connection.session.ping(b"").await
+            }
+        };
+
+        if result.is_err() {
+            return;
+        }
+    };
+
+    let _ = connection.session.close(reason).await;
+}
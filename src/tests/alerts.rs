@@ -0,0 +1,57 @@
+use bigdecimal::BigDecimal;
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use crate::alerting::{AlertCondition, AlertOperator, AlertTracker, AlertTransition};
+
+fn at(minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2024, 7, 1).unwrap().and_hms_opt(12, minute, 0).unwrap()
+}
+
+fn above(threshold: i32, hysteresis: i32, minutes: i64) -> AlertCondition {
+    AlertCondition {
+        operator: AlertOperator::Gt,
+        threshold: BigDecimal::from(threshold),
+        hysteresis: BigDecimal::from(hysteresis),
+        min_duration: Duration::minutes(minutes),
+    }
+}
+
+#[test]
+fn test_alert_opens_and_closes_with_hysteresis() {
+    let condition = above(35, 2, 0);
+    let mut tracker = AlertTracker::default();
+
+    assert_eq!(tracker.observe(&condition, at(0), &BigDecimal::from(30)), None);
+    assert_eq!(
+        tracker.observe(&condition, at(1), &BigDecimal::from(36)),
+        Some(AlertTransition::Opened { ts: at(1), value: BigDecimal::from(36) })
+    );
+    assert_eq!(tracker.observe(&condition, at(2), &BigDecimal::from(34)), None);
+    assert_eq!(
+        tracker.observe(&condition, at(3), &BigDecimal::from(32)),
+        Some(AlertTransition::Closed { ts: at(3), value: BigDecimal::from(32) })
+    );
+}
+
+#[test]
+fn test_alert_waits_for_min_duration() {
+    let condition = above(35, 0, 5);
+    let mut tracker = AlertTracker::default();
+
+    assert_eq!(tracker.observe(&condition, at(0), &BigDecimal::from(36)), None);
+    assert_eq!(tracker.observe(&condition, at(3), &BigDecimal::from(34)), None);
+    assert_eq!(tracker.observe(&condition, at(4), &BigDecimal::from(37)), None);
+    assert_eq!(tracker.observe(&condition, at(8), &BigDecimal::from(38)), None);
+    assert_eq!(
+        tracker.observe(&condition, at(9), &BigDecimal::from(39)),
+        Some(AlertTransition::Opened { ts: at(4), value: BigDecimal::from(37) })
+    );
+}
+
+#[test]
+fn test_alert_ignores_old_readings() {
+    let condition = above(35, 0, 0);
+    let mut tracker = AlertTracker { last_ts: Some(at(10)), ..AlertTracker::default() };
+
+    assert_eq!(tracker.observe(&condition, at(5), &BigDecimal::from(40)), None);
+    assert!(!tracker.open);
+}
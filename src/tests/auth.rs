@@ -0,0 +1,72 @@
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{web, App, HttpResponse};
+use crate::auth::{generate_api_key, hash_api_key, required_scope, ApiKeyAuth, Identity, Scope};
+
+#[test]
+fn test_required_scope() {
+    assert_eq!(required_scope(&Method::GET, "/api/measurements"), Some(&[Scope::Read][..]));
+    assert_eq!(required_scope(&Method::POST, "/api/measurements"), Some(&[Scope::Write][..]));
+    assert_eq!(required_scope(&Method::POST, "/api/measurements/reviews"), Some(&[Scope::Admin][..]));
+    assert_eq!(required_scope(&Method::POST, "/api/measurements/import"), Some(&[Scope::Write][..]));
+    assert_eq!(required_scope(&Method::GET, "/api/measurements/reviews"), Some(&[Scope::Read][..]));
+    assert_eq!(required_scope(&Method::DELETE, "/api/measurements/1"), Some(&[Scope::Admin][..]));
+    assert_eq!(required_scope(&Method::POST, "/api/meteostations"), Some(&[Scope::Admin][..]));
+    assert_eq!(required_scope(&Method::GET, "/api/api_keys"), Some(&[Scope::Admin][..]));
+    assert_eq!(required_scope(&Method::GET, "/api/webhooks/1/deliveries"), Some(&[Scope::Admin][..]));
+    assert_eq!(required_scope(&Method::GET, "/api/api_keys/me"), Some(&[Scope::Read, Scope::Write][..]));
+    assert_eq!(required_scope(&Method::GET, "/api/measurements/ws"), Some(&[Scope::Read, Scope::Write][..]));
+    assert_eq!(required_scope(&Method::GET, "/api-doc/openapi.json"), None);
+    assert_eq!(required_scope(&Method::GET, "/swagger-ui/index.html"), None);
+    assert_eq!(required_scope(&Method::OPTIONS, "/api/sensors"), None);
+    assert_eq!(required_scope(&Method::GET, "/api"), Some(&[Scope::Read][..]));
+    assert_eq!(required_scope(&Method::DELETE, "/apiary"), Some(&[Scope::Admin][..]));
+}
+
+#[actix_web::test]
+async fn test_percent_encoded_paths_require_a_key() {
+    let app = init_service(
+        App::new()
+            .wrap(ApiKeyAuth)
+            .route("/api/measurements", web::get().to(HttpResponse::Ok))
+            .route("/api/meteostations/{id}", web::delete().to(HttpResponse::NoContent))
+            .route("/api-doc/openapi.json", web::get().to(HttpResponse::Ok)),
+    )
+        .await;
+
+    for (method, uri) in [
+        (Method::GET, "/api/measurements"),
+        (Method::GET, "/%61pi/measurements"),
+        (Method::GET, "/api/%6Deasurements"),
+        (Method::DELETE, "/%61pi/meteostations/1"),
+    ] {
+        let req = TestRequest::default().method(method).uri(uri).to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED, "{}", uri);
+    }
+
+    let req = TestRequest::get().uri("/api-doc/openapi.json").to_request();
+    assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+}
+
+#[test]
+fn test_identity_scopes() {
+    let gateway = Identity { key_id: 1, name: String::from("gateway"), scopes: vec![Scope::Write], station_id: Some(1) };
+    let admin = Identity { key_id: 2, name: String::from("admin"), scopes: vec![Scope::Admin], station_id: None };
+
+    assert!(gateway.allows(Scope::Write));
+    assert!(!gateway.allows(Scope::Read));
+    assert!(admin.allows(Scope::Read));
+    assert!(admin.allows(Scope::Write));
+}
+
+#[test]
+fn test_generated_keys_are_unique_and_hashed() {
+    let first = generate_api_key();
+    let second = generate_api_key();
+
+    assert!(first.starts_with("mk_"));
+    assert_ne!(first, second);
+    assert_eq!(hash_api_key(&first).len(), 64);
+    assert_eq!(hash_api_key(&first), hash_api_key(&first));
+}
@@ -0,0 +1,58 @@
+use chrono::NaiveDate;
+use crate::csv::{escape_field, parse_records, write_record, CsvRecord};
+use crate::handlers::measurement_import::{parse_delimiter, parse_timestamp};
+
+fn fields(values: &[&str]) -> Result<Vec<String>, String> {
+    Ok(values.iter().map(|value| value.to_string()).collect())
+}
+
+#[test]
+fn test_field_escaping() {
+    assert_eq!(escape_field("Vnukovo"), "Vnukovo");
+    assert_eq!(escape_field("12.5"), "12.5");
+    assert_eq!(escape_field("Moscow, VDNKh"), "\"Moscow, VDNKh\"");
+    assert_eq!(escape_field("the \"roof\" sensor"), "\"the \"\"roof\"\" sensor\"");
+    assert_eq!(escape_field("two\nlines"), "\"two\nlines\"");
+}
+
+#[test]
+fn test_record_writing() {
+    let mut out = String::new();
+    write_record(&mut out, &["station_id", "station_name", "value"]);
+    write_record(&mut out, &[String::from("1"), String::from("Moscow, VDNKh"), String::new()]);
+
+    assert_eq!(out, "station_id,station_name,value\r\n1,\"Moscow, VDNKh\",\r\n");
+}
+
+#[test]
+fn test_record_parsing() {
+    let input = "ts;value\r\n2024-01-01;1,5\n\n\"VDNKh\n\"\"north\"\"\";2\n\"open;3\n";
+    let records = parse_records(input, ';');
+
+    assert_eq!(records[0], CsvRecord { line: 1, fields: fields(&["ts", "value"]) });
+    assert_eq!(records[1], CsvRecord { line: 2, fields: fields(&["2024-01-01", "1,5"]) });
+    assert_eq!(records[2], CsvRecord { line: 4, fields: fields(&["VDNKh\n\"north\"", "2"]) });
+    assert_eq!(records[3].line, 6);
+    assert!(records[3].fields.is_err());
+    assert_eq!(records.len(), 4);
+
+    let records = parse_records("\"a\"b,c\nd,e", ',');
+    assert!(records[0].fields.is_err());
+    assert_eq!(records[1], CsvRecord { line: 2, fields: fields(&["d", "e"]) });
+}
+
+#[test]
+fn test_import_options() {
+    assert_eq!(parse_delimiter(None).unwrap(), ',');
+    assert_eq!(parse_delimiter(Some(";")).unwrap(), ';');
+    assert_eq!(parse_delimiter(Some("tab")).unwrap(), '\t');
+    assert!(parse_delimiter(Some(";;")).is_err());
+    assert!(parse_delimiter(Some("\"")).is_err());
+
+    let ts = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(3, 4, 0).unwrap();
+    assert_eq!(parse_timestamp("2024-01-02T03:04:00Z", None).unwrap(), ts);
+    assert_eq!(parse_timestamp("2024-01-02T06:04:00+03:00", None).unwrap(), ts);
+    assert_eq!(parse_timestamp("02.01.2024 03:04", Some("%d.%m.%Y %H:%M")).unwrap(), ts);
+    assert_eq!(parse_timestamp("02.01.2024 06:04 +0300", Some("%d.%m.%Y %H:%M %z")).unwrap(), ts);
+    assert!(parse_timestamp("2024-01-02 03:04", None).is_err());
+}
@@ -0,0 +1,50 @@
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+use crate::derived::{dew_point, heat_index, relative_humidity, wind_chill, Conditions, DerivedVariable};
+
+fn assert_close(actual: f64, expected: f64) {
+    assert!((actual - expected).abs() < 0.01, "{} is not close to {}", actual, expected);
+}
+
+#[test]
+fn test_dew_point_and_relative_humidity() {
+    let td = dew_point(20.0, 50.0).unwrap();
+    assert_close(td, 9.26);
+    assert_close(relative_humidity(20.0, td).unwrap(), 50.0);
+    assert_close(relative_humidity(20.0, 25.0).unwrap(), 100.0);
+    assert_eq!(dew_point(20.0, 0.0), None);
+}
+
+#[test]
+fn test_heat_index() {
+    // 90 °F at 70 % is 106 °F in the NWS heat index chart.
+    assert_close(heat_index((90.0 - 32.0) * 5.0 / 9.0, 70.0), 41.07);
+    // Below 80 °F the simple formula stays close to the air temperature.
+    assert_close(heat_index(20.0, 50.0), 19.36);
+}
+
+#[test]
+fn test_wind_chill() {
+    assert_close(wind_chill(-10.0, 20.0 / 3.6).unwrap(), -17.86);
+    assert_eq!(wind_chill(15.0, 10.0), None);
+    assert_eq!(wind_chill(-5.0, 1.0), None);
+}
+
+#[test]
+fn test_derived_variables() {
+    let cold = Conditions { air_temperature: Some(-10.0), wind_speed: Some(20.0 / 3.6), ..Conditions::default() };
+    let mild = Conditions { air_temperature: Some(18.0), relative_humidity: Some(60.0), ..Conditions::default() };
+    let hot = Conditions { air_temperature: Some(35.0), dew_point: Some(25.0), ..Conditions::default() };
+
+    assert_eq!(
+        DerivedVariable::ApparentTemperature.compute_decimal(&cold),
+        Some(BigDecimal::from_str("-17.86").unwrap())
+    );
+    assert_eq!(DerivedVariable::ApparentTemperature.compute(&mild), Some(18.0));
+    assert_eq!(DerivedVariable::WindChill.compute(&mild), None);
+    assert_eq!(DerivedVariable::DewPoint.compute(&cold), None);
+    // Heat index falls back to the humidity implied by the dew point.
+    assert!(DerivedVariable::HeatIndex.compute(&hot).unwrap() > 35.0);
+    assert_eq!(DerivedVariable::from_type_id(-5), Some(DerivedVariable::ApparentTemperature));
+    assert_eq!(DerivedVariable::from_type_id(1), None);
+}
@@ -0,0 +1,39 @@
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+use actix_web::{web, App, HttpResponse};
+use serde_json::Value;
+use crate::error::json_error_handler;
+
+async fn echo(body: web::Json<Value>) -> HttpResponse {
+    HttpResponse::Ok().json(body.into_inner())
+}
+
+#[actix_web::test]
+async fn test_json_errors_keep_their_status() {
+    let app = init_service(
+        App::new()
+            .app_data(web::JsonConfig::default().limit(16).error_handler(json_error_handler))
+            .route("/", web::post().to(echo)),
+    )
+        .await;
+
+    for (content_type, body, status, code) in [
+        ("application/json", r#"{"a":1}"#, StatusCode::OK, None),
+        ("application/json", r#"{"a":"0123456789abcdef"}"#, StatusCode::PAYLOAD_TOO_LARGE, Some("payload_too_large")),
+        ("text/plain", r#"{"a":1}"#, StatusCode::UNSUPPORTED_MEDIA_TYPE, Some("unsupported_media_type")),
+        ("application/json", r#"{"a":"#, StatusCode::BAD_REQUEST, Some("validation_failed")),
+    ] {
+        let req = TestRequest::post()
+            .uri("/")
+            .insert_header(("content-type", content_type))
+            .set_payload(body)
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), status, "{}", body);
+
+        if let Some(code) = code {
+            let problem: Value = read_body_json(res).await;
+            assert_eq!(problem["code"], code);
+        }
+    }
+}
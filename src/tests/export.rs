@@ -0,0 +1,139 @@
+use std::io::Cursor;
+use std::str::FromStr;
+use std::sync::Arc;
+use actix_web::web::{Bytes, Query};
+use arrow_array::{Array, Decimal128Array, Int32Array, RecordBatch, TimestampMicrosecondArray};
+use arrow_ipc::reader::StreamReader;
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, NaiveDateTime};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use crate::handlers::measurement_export::{
+    check_export_query, decimal_value, export_schema, record_batch, ExportEncoder, ExportRecord, EXPORT_CHUNK_SIZE, PARQUET_ROW_GROUP_SIZE,
+};
+use crate::models::{ExportFormat, Measurement, MeasurementQuery};
+use crate::qc::QcFlag;
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+#[test]
+fn test_decimal_values() {
+    assert_eq!(decimal_value(&decimal("21.5")), Some(21_500_000));
+    assert_eq!(decimal_value(&decimal("-0.0000004")), Some(0));
+    assert_eq!(decimal_value(&decimal("1.2345678")), Some(1_234_568));
+    assert_eq!(decimal_value(&decimal("1e40")), None);
+}
+
+fn record(ts: NaiveDateTime, calibrated_value: &str) -> ExportRecord {
+    ExportRecord {
+        station_id: 1,
+        station_name: String::from("VDNKh"),
+        sensor_name: String::from("t1"),
+        type_name: None,
+        units: None,
+        measurement: Measurement {
+            sensor_inventory_number: String::from("7"),
+            station_id: None,
+            value: decimal("20"),
+            calibrated_value: Some(decimal(calibrated_value)),
+            units: None,
+            ts,
+            r#type: None,
+            qc_flag: QcFlag::Good,
+            qc_checks: Vec::new(),
+            corrected_value: None,
+            qc_note: None,
+            reviewed_by: None,
+            reviewed_at: None,
+        },
+    }
+}
+
+#[test]
+fn test_record_batch() {
+    let ts = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap().and_hms_opt(3, 4, 5).unwrap();
+    let record = record(ts, "20.25");
+
+    let batch = record_batch(Arc::new(export_schema()), &[record]).unwrap();
+    assert_eq!(batch.num_rows(), 1);
+
+    let station_ids = batch.column(0).as_any().downcast_ref::<Int32Array>().unwrap();
+    assert_eq!(station_ids.value(0), 1);
+    let type_ids = batch.column(4).as_any().downcast_ref::<Int32Array>().unwrap();
+    assert!(type_ids.is_null(0));
+    let timestamps = batch.column(7).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+    assert_eq!(timestamps.value(0), ts.and_utc().timestamp_micros());
+    let values = batch.column(8).as_any().downcast_ref::<Decimal128Array>().unwrap();
+    assert_eq!(values.value_as_string(0), "20.250000");
+}
+
+/// Encodes more readings than fit in one Parquet row group, in export-sized chunks, and
+/// concatenates the chunks the way the response body does.
+fn encode_export(format: ExportFormat, rows: usize) -> Vec<u8> {
+    let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let records: Vec<ExportRecord> = (0..rows)
+        .map(|i| record(start + chrono::Duration::seconds(i as i64), &format!("{}.5", i)))
+        .collect();
+
+    let mut encoder = ExportEncoder::new(format).unwrap();
+    let mut file = Vec::new();
+    let mut chunks = 0;
+    for chunk in records.chunks(EXPORT_CHUNK_SIZE as usize) {
+        let bytes = encoder.encode(chunk).unwrap();
+        chunks += usize::from(!bytes.is_empty());
+        file.extend(bytes);
+    }
+    let footer = encoder.finish().unwrap();
+    chunks += usize::from(!footer.is_empty());
+    file.extend(footer);
+
+    // Parquet only writes through once the first row group is complete.
+    assert!(chunks > 1);
+    file
+}
+
+fn assert_readings(batches: Vec<RecordBatch>, rows: usize) {
+    assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), rows);
+
+    let values: Vec<String> = batches
+        .iter()
+        .flat_map(|batch| {
+            let values = batch.column(8).as_any().downcast_ref::<Decimal128Array>().unwrap();
+            (0..values.len()).map(|i| values.value_as_string(i)).collect::<Vec<_>>()
+        })
+        .collect();
+    for (i, value) in values.iter().enumerate() {
+        assert_eq!(value, &format!("{}.500000", i));
+    }
+}
+
+#[test]
+fn test_arrow_export_round_trip() {
+    let rows = PARQUET_ROW_GROUP_SIZE + 1500;
+    let file = encode_export(ExportFormat::Arrow, rows);
+
+    let reader = StreamReader::try_new(Cursor::new(file), None).unwrap();
+    assert_eq!(reader.schema().as_ref(), &export_schema());
+    assert_readings(reader.map(Result::unwrap).collect(), rows);
+}
+
+#[test]
+fn test_parquet_export_round_trip() {
+    let rows = PARQUET_ROW_GROUP_SIZE + 1500;
+    let file = encode_export(ExportFormat::Parquet, rows);
+
+    let builder = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(file)).unwrap();
+    assert_eq!(builder.metadata().num_row_groups(), 2);
+    assert_readings(builder.build().unwrap().map(Result::unwrap).collect(), rows);
+}
+
+#[test]
+fn test_export_query() {
+    let check = |query: &str| check_export_query(&Query::<MeasurementQuery>::from_query(query).unwrap()).is_ok();
+
+    assert!(check("type=1&order=desc&from=2024-01-01T00:00:00Z"));
+    assert!(!check("limit=10"));
+    assert!(!check("cursor=abc"));
+    assert!(!check("type=-1"));
+}
@@ -0,0 +1,79 @@
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+use crate::formula::{Formula, FormulaError, validate_formulas};
+
+#[test]
+fn test_default_formula_returns_raw_value() {
+    let formula = Formula::parse("value").unwrap();
+
+    assert_eq!(formula.eval(21.5), Ok(21.5));
+}
+
+#[test]
+fn test_operator_precedence() {
+    let formula = Formula::parse("2 + 3 * value ^ 2 - -1").unwrap();
+
+    assert_eq!(formula.eval(2.0), Ok(15.0));
+    assert_eq!(Formula::parse("-2 ^ 2").unwrap().eval(0.0), Ok(-4.0));
+    assert_eq!(Formula::parse("2 ^ 3 ^ 2").unwrap().eval(0.0), Ok(512.0));
+}
+
+#[test]
+fn test_functions_and_constants() {
+    let formula = Formula::parse("max(sqrt(abs(value)), 1) * round(pi)").unwrap();
+
+    assert_eq!(formula.eval(-16.0), Ok(12.0));
+    assert_eq!(Formula::parse("ln(e)").unwrap().eval(0.0), Ok(1.0));
+    assert_eq!(Formula::parse("1.5e2 * value").unwrap().eval(2.0), Ok(300.0));
+}
+
+#[test]
+fn test_invalid_formulas_are_rejected() {
+    assert_eq!(Formula::parse("ax^2"), Err(FormulaError::UnknownIdentifier("ax".to_string())));
+    assert_eq!(Formula::parse("value +"), Err(FormulaError::UnexpectedEnd));
+    assert_eq!(Formula::parse("value; drop"), Err(FormulaError::UnexpectedChar(';', 5)));
+    assert!(matches!(Formula::parse("(value"), Err(FormulaError::UnexpectedEnd)));
+    assert!(matches!(Formula::parse("value value"), Err(FormulaError::UnexpectedToken(_, 6))));
+    assert!(matches!(Formula::parse("pow(value)"), Err(FormulaError::WrongArity { expected: 2, found: 1, .. })));
+}
+
+#[test]
+fn test_formula_size_is_bounded() {
+    let nested = |depth: usize| format!("{}value{}", "(".repeat(depth), ")".repeat(depth));
+
+    assert_eq!(Formula::parse(&nested(60)).unwrap().eval(2.0), Ok(2.0));
+    assert_eq!(Formula::parse(&nested(100)), Err(FormulaError::TooDeep));
+    assert_eq!(Formula::parse(&"-".repeat(100)), Err(FormulaError::TooDeep));
+    assert_eq!(Formula::parse(&"abs(".repeat(80)), Err(FormulaError::TooDeep));
+    assert_eq!(Formula::parse(&"(".repeat(100_000)), Err(FormulaError::TooLong));
+    assert_eq!(Formula::parse(&"value+".repeat(100)), Err(FormulaError::TooLong));
+
+    let sum = format!("value{}", "+1".repeat(250));
+    assert_eq!(Formula::parse(&sum).unwrap().eval(0.0), Ok(250.0));
+}
+
+#[test]
+fn test_non_finite_results_are_errors() {
+    let formula = Formula::parse("1 / value").unwrap();
+
+    assert_eq!(formula.eval(0.0), Err(FormulaError::NotFinite));
+    assert_eq!(Formula::parse("sqrt(value)").unwrap().eval(-1.0), Err(FormulaError::NotFinite));
+}
+
+#[test]
+fn test_apply_to_decimal() {
+    let formula = Formula::parse("value * 2 + 0.5").unwrap();
+
+    let calibrated = formula.apply(&BigDecimal::from_str("10.25").unwrap()).unwrap();
+
+    assert_eq!(calibrated, BigDecimal::from_str("21").unwrap());
+}
+
+#[test]
+fn test_validate_formulas_skips_defaults() {
+    assert!(validate_formulas(vec![None, Some("value / 10")]).is_ok());
+    assert_eq!(
+        validate_formulas(vec![Some("value"), Some("ax^2")]),
+        Err((1, FormulaError::UnknownIdentifier("ax".to_string())))
+    );
+}
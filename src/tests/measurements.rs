@@ -0,0 +1,104 @@
+use chrono::NaiveDate;
+use bigdecimal::BigDecimal;
+use crate::events::MeasurementEvent;
+use actix_web::web::Query;
+use crate::models::{Measurement, MeasurementQuery, MeasurementStreamQuery};
+use crate::qc::QcFlag;
+use crate::handlers::measurements::{MeasurementCursor, SensorAssignment, check_aggregate_range, parse_interval};
+
+#[test]
+fn test_cursor_roundtrip() {
+    let cursor = MeasurementCursor {
+        ts: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_micro_opt(12, 30, 0, 250).unwrap(),
+        sensor_inventory_number: String::from("INV|42"),
+        r#type: Some(3),
+    };
+
+    let decoded = MeasurementCursor::decode(&cursor.encode()).unwrap();
+
+    assert_eq!(decoded.ts, cursor.ts);
+    assert_eq!(decoded.sensor_inventory_number, cursor.sensor_inventory_number);
+    assert_eq!(decoded.r#type, cursor.r#type);
+}
+
+#[test]
+fn test_invalid_cursor() {
+    assert!(MeasurementCursor::decode("zz").is_none());
+    assert!(MeasurementCursor::decode("abc").is_none());
+    assert!(MeasurementCursor::decode("").is_none());
+}
+
+#[test]
+fn test_parse_interval() {
+    assert_eq!(parse_interval("30s"), Some(30));
+    assert_eq!(parse_interval("15m"), Some(900));
+    assert_eq!(parse_interval("1h"), Some(3600));
+    assert_eq!(parse_interval("2d"), Some(172800));
+    assert_eq!(parse_interval("1w"), Some(604800));
+    assert_eq!(parse_interval("0h"), None);
+    assert_eq!(parse_interval("h"), None);
+    assert_eq!(parse_interval("10"), None);
+    assert_eq!(parse_interval("1y"), None);
+}
+
+#[test]
+fn test_sensor_assignment_window() {
+    let at = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap().and_hms_opt(0, 0, 0).unwrap();
+    let assignment = SensorAssignment { station_id: 1, sensor_id: 1, added_ts: Some(at(2)), removed_ts: Some(at(10)) };
+
+    assert!(!assignment.covers(at(1)));
+    assert!(assignment.covers(at(2)));
+    assert!(assignment.covers(at(9)));
+    assert!(!assignment.covers(at(10)));
+
+    let open_ended = SensorAssignment { station_id: 1, sensor_id: 1, added_ts: None, removed_ts: None };
+    assert!(open_ended.covers(at(1)));
+}
+
+#[test]
+fn test_stream_filter() {
+    let event = MeasurementEvent {
+        id: 1,
+        station_id: 2,
+        sensor_id: 3,
+        measurement: Measurement {
+            sensor_inventory_number: String::from("7"),
+            station_id: None,
+            value: BigDecimal::from(1),
+            calibrated_value: None,
+            units: None,
+            ts: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            r#type: Some(4),
+            qc_flag: QcFlag::Good,
+            qc_checks: Vec::new(),
+            corrected_value: None,
+            qc_note: None,
+            reviewed_by: None,
+            reviewed_at: None,
+        },
+    };
+    let filter = |meteostation, sensor, r#type| MeasurementStreamQuery { meteostation, sensor, r#type };
+
+    assert!(filter(None, None, None).matches(&event));
+    assert!(filter(Some(2), Some(3), Some(4)).matches(&event));
+    assert!(!filter(Some(1), None, None).matches(&event));
+    assert!(!filter(None, Some(1), None).matches(&event));
+    assert!(!filter(None, None, Some(1)).matches(&event));
+}
+
+#[test]
+fn test_aggregate_range() {
+    let check = |query: &str, interval: &str| {
+        let query = Query::<MeasurementQuery>::from_query(query).unwrap();
+        check_aggregate_range(&query, parse_interval(interval).unwrap()).is_ok()
+    };
+    let range = "from=2024-01-01T00:00:00Z&to=2024-02-01T00:00:00Z";
+
+    assert!(check(range, "1h"));
+    assert!(!check(range, "1m"));
+    assert!(!check("from=2024-01-01T00:00:00Z", "1h"));
+    assert!(!check("from=2024-02-01T00:00:00Z&to=2024-01-01T00:00:00Z", "1h"));
+    assert!(!check(&format!("{}&limit=10", range), "1h"));
+    assert!(!check(&format!("{}&order=desc", range), "1h"));
+    assert!(!check(&format!("{}&cursor=abc", range), "1h"));
+}
@@ -0,0 +1,51 @@
+use std::str::FromStr;
+use bigdecimal::BigDecimal;
+use crate::handlers::meteostations::{parse_bbox, parse_point, station_feature, BoundingBox};
+use crate::models::{Meteostation, MeteostationMetadata};
+
+#[test]
+fn test_point_parsing() {
+    assert_eq!(parse_point("55.75,37.62").unwrap(), (55.75, 37.62));
+    assert_eq!(parse_point(" -33.9 , 151.2 ").unwrap(), (-33.9, 151.2));
+    assert!(parse_point("55.75").is_err());
+    assert!(parse_point("91,0").is_err());
+    assert!(parse_point("0,181").is_err());
+    assert!(parse_point("north,east").is_err());
+}
+
+#[test]
+fn test_bbox_parsing() {
+    assert_eq!(
+        parse_bbox("30,50,40,60").unwrap(),
+        BoundingBox { min_lon: 30.0, min_lat: 50.0, max_lon: 40.0, max_lat: 60.0 }
+    );
+    // Crossing the antimeridian.
+    assert_eq!(parse_bbox("170,-20,-170,20").unwrap().min_lon, 170.0);
+    assert!(parse_bbox("30,60,40,50").is_err());
+    assert!(parse_bbox("30,50,40").is_err());
+    assert!(parse_bbox("30,50,40,60,70").is_err());
+    assert!(parse_bbox("30,50,200,60").is_err());
+    assert!(parse_bbox("30,50,NaN,60").is_err());
+}
+
+#[test]
+fn test_station_feature() {
+    let station = Meteostation {
+        id: 7,
+        name: String::from("Vnukovo"),
+        longitude: BigDecimal::from_str("37.2615").unwrap(),
+        latitude: BigDecimal::from_str("55.5915").unwrap(),
+        metadata: MeteostationMetadata { icao_code: Some(String::from("UUWW")), ..Default::default() },
+        distance_km: None,
+    };
+
+    let feature = serde_json::to_value(station_feature(station, vec![], None)).unwrap();
+    assert_eq!(feature["type"], "Feature");
+    assert_eq!(feature["id"], 7);
+    assert_eq!(feature["geometry"]["type"], "Point");
+    assert_eq!(feature["geometry"]["coordinates"], serde_json::json!([37.2615, 55.5915]));
+    assert_eq!(feature["properties"]["name"], "Vnukovo");
+    assert_eq!(feature["properties"]["icao_code"], "UUWW");
+    assert_eq!(feature["properties"]["sensors"], serde_json::json!([]));
+    assert!(feature["properties"].get("latest").is_none());
+}
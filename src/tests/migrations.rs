@@ -0,0 +1,64 @@
+use std::env;
+use dotenv::dotenv;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Executor, PgPool};
+use crate::config::{adopt_existing_schema, applied_schema_version, expected_schema_version, run_migrations};
+
+/// Pool confined to a scratch schema of the development database.
+async fn scratch_pool(schema: &str) -> PgPool {
+    dotenv().ok();
+    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+
+    let admin = PgPool::connect(&database_url).await.unwrap();
+    admin
+        .execute(&*format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}", schema))
+        .await
+        .unwrap();
+
+    let search_path = format!("SET search_path TO {}", schema);
+    PgPoolOptions::new()
+        .max_connections(1)
+        .after_connect(move |conn, _| {
+            let search_path = search_path.clone();
+            Box::pin(async move { conn.execute(&*search_path).await.map(|_| ()) })
+        })
+        .connect(&database_url)
+        .await
+        .unwrap()
+}
+
+async fn drop_schema(pool: PgPool, schema: &str) {
+    pool.execute(&*format!("DROP SCHEMA {} CASCADE", schema)).await.unwrap();
+}
+
+#[actix_web::test]
+async fn test_existing_schema_is_adopted() {
+    let schema = format!("adopt_test_{}", std::process::id());
+    let pool = scratch_pool(&schema).await;
+
+    // A database from before migrations were embedded: the baseline tables, no history.
+    pool.execute(include_str!("../../migrations/0001_create_schema.sql")).await.unwrap();
+    pool.execute("INSERT INTO sensors (name) VALUES ('t1')").await.unwrap();
+    assert_eq!(applied_schema_version(&pool).await.unwrap(), None);
+
+    run_migrations(&pool).await.unwrap();
+
+    assert_eq!(applied_schema_version(&pool).await.unwrap(), expected_schema_version());
+    let sensors: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM sensors").fetch_one(&pool).await.unwrap();
+    assert_eq!(sensors, 1);
+    assert!(!adopt_existing_schema(&pool).await.unwrap());
+
+    drop_schema(pool, &schema).await;
+}
+
+#[actix_web::test]
+async fn test_empty_database_is_migrated() {
+    let schema = format!("migrate_test_{}", std::process::id());
+    let pool = scratch_pool(&schema).await;
+
+    assert!(!adopt_existing_schema(&pool).await.unwrap());
+    run_migrations(&pool).await.unwrap();
+    assert_eq!(applied_schema_version(&pool).await.unwrap(), expected_schema_version());
+
+    drop_schema(pool, &schema).await;
+}
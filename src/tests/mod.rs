@@ -1 +1,14 @@
-mod sensors;
\ No newline at end of file
+mod sensors;
+mod formula;
+mod measurements;
+mod auth;
+mod alerts;
+mod webhooks;
+mod qc;
+mod derived;
+mod units;
+mod meteostations;
+mod csv;
+mod export;
+mod migrations;
+mod errors;
\ No newline at end of file
@@ -0,0 +1,50 @@
+use bigdecimal::BigDecimal;
+use crate::qc::{QcFlag, QcLimits, FLATLINE_CHECK, RANGE_CHECK, STEP_CHECK};
+
+fn values(values: &[i32]) -> Vec<BigDecimal> {
+    values.iter().map(|v| BigDecimal::from(*v)).collect()
+}
+
+#[test]
+fn test_range_check() {
+    let limits = QcLimits {
+        min: Some(BigDecimal::from(-60)),
+        max: Some(BigDecimal::from(60)),
+        ..QcLimits::default()
+    };
+
+    assert_eq!(limits.check(&[], &BigDecimal::from(20)), (QcFlag::Good, vec![]));
+    assert_eq!(limits.check(&[], &BigDecimal::from(60)), (QcFlag::Good, vec![]));
+    assert_eq!(limits.check(&[], &BigDecimal::from(61)), (QcFlag::Bad, vec![RANGE_CHECK]));
+    assert_eq!(limits.check(&[], &BigDecimal::from(-61)), (QcFlag::Bad, vec![RANGE_CHECK]));
+}
+
+#[test]
+fn test_step_check() {
+    let limits = QcLimits { max_step: Some(BigDecimal::from(5)), ..QcLimits::default() };
+
+    assert_eq!(limits.history_len(), 1);
+    assert_eq!(limits.check(&[], &BigDecimal::from(100)), (QcFlag::Good, vec![]));
+    assert_eq!(limits.check(&values(&[20]), &BigDecimal::from(25)), (QcFlag::Good, vec![]));
+    assert_eq!(limits.check(&values(&[20]), &BigDecimal::from(26)), (QcFlag::Suspect, vec![STEP_CHECK]));
+    assert_eq!(limits.check(&values(&[20]), &BigDecimal::from(14)), (QcFlag::Suspect, vec![STEP_CHECK]));
+}
+
+#[test]
+fn test_flatline_check() {
+    let limits = QcLimits {
+        max: Some(BigDecimal::from(10)),
+        flatline_count: Some(3),
+        ..QcLimits::default()
+    };
+
+    assert_eq!(limits.history_len(), 2);
+    assert_eq!(limits.check(&values(&[7]), &BigDecimal::from(7)), (QcFlag::Good, vec![]));
+    assert_eq!(limits.check(&values(&[6, 7]), &BigDecimal::from(7)), (QcFlag::Good, vec![]));
+    assert_eq!(limits.check(&values(&[1, 7, 7]), &BigDecimal::from(7)), (QcFlag::Suspect, vec![FLATLINE_CHECK]));
+    // The worst flag wins when several checks fail.
+    assert_eq!(
+        limits.check(&values(&[12, 12]), &BigDecimal::from(12)),
+        (QcFlag::Bad, vec![RANGE_CHECK, FLATLINE_CHECK])
+    );
+}
@@ -1,3 +1,5 @@
+#![allow(unused_imports, dead_code)]
+
 use actix_web::{test, web, App};
 use sqlx::{Executor, PgPool};
 use serde_json::json;
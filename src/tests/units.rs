@@ -0,0 +1,60 @@
+use bigdecimal::BigDecimal;
+use std::str::FromStr;
+use crate::units::{Conversion, Unit, UnitSelection, UnitSystem};
+
+fn decimal(value: &str) -> BigDecimal {
+    BigDecimal::from_str(value).unwrap()
+}
+
+fn convert(value: &str, from: Unit, to: Unit) -> BigDecimal {
+    Conversion::between(from, to).unwrap().apply(&decimal(value))
+}
+
+#[test]
+fn test_unit_parsing() {
+    assert_eq!("°C".parse::<Unit>().unwrap(), Unit::Celsius);
+    assert_eq!("C".parse::<Unit>().unwrap(), Unit::Celsius);
+    assert_eq!("KTS".parse::<Unit>().unwrap(), Unit::Knots);
+    assert_eq!(" hPa ".parse::<Unit>().unwrap(), Unit::Hectopascal);
+    assert!("furlongs".parse::<Unit>().is_err());
+}
+
+#[test]
+fn test_conversions() {
+    assert_eq!(convert("20", Unit::Celsius, Unit::Fahrenheit), decimal("68"));
+    assert_eq!(convert("-40", Unit::Fahrenheit, Unit::Celsius), decimal("-40"));
+    assert_eq!(convert("0", Unit::Celsius, Unit::Kelvin), decimal("273.15"));
+    assert_eq!(convert("10", Unit::MetersPerSecond, Unit::KilometersPerHour), decimal("36"));
+    assert_eq!(convert("10", Unit::Knots, Unit::MetersPerSecond), decimal("5.144444"));
+    assert_eq!(convert("1013.25", Unit::Hectopascal, Unit::InchesOfMercury), decimal("29.921256"));
+    assert_eq!(convert("760", Unit::MillimetersOfMercury, Unit::Hectopascal), decimal("1013.250144"));
+    assert_eq!(convert("1", Unit::Inches, Unit::Millimeters), decimal("25.4"));
+    assert_eq!(convert("760", Unit::MillimetersOfMercury, Unit::InchesOfMercury), decimal("29.92126"));
+    assert_eq!(convert("29.92", Unit::InchesOfMercury, Unit::MillimetersOfMercury), decimal("759.968"));
+    assert_eq!(convert("760.5", Unit::MillimetersOfMercury, Unit::MillimetersOfMercury), decimal("760.5"));
+    assert_eq!(convert("29.92", Unit::InchesOfMercury, Unit::InchesOfMercury), decimal("29.92"));
+    assert_eq!(convert("-12.5", Unit::Fahrenheit, Unit::Fahrenheit), decimal("-12.5"));
+    assert_eq!(Conversion::between(Unit::Celsius, Unit::Knots), None);
+}
+
+#[test]
+fn test_sum_conversion() {
+    let conversion = Conversion::between(Unit::Celsius, Unit::Fahrenheit).unwrap();
+    // 10 °C + 20 °C is 50 °F + 68 °F.
+    assert_eq!(conversion.apply_sum(&decimal("30"), 2), decimal("118"));
+}
+
+#[test]
+fn test_unit_selection() {
+    let selection = UnitSelection::parse("imperial, 3:kn").unwrap();
+    assert_eq!(selection.system, Some(UnitSystem::Imperial));
+    assert_eq!(selection.types.get(&3), Some(&Unit::Knots));
+
+    assert_eq!(selection.conversion(1, "°C").unwrap(), Conversion::between(Unit::Celsius, Unit::Fahrenheit));
+    assert_eq!(selection.conversion(3, "m/s").unwrap(), Conversion::between(Unit::MetersPerSecond, Unit::Knots));
+    assert_eq!(selection.conversion(2, "%").unwrap(), None);
+    assert_eq!(selection.conversion(4, "W/m²").unwrap(), None);
+    assert!(selection.conversion(3, "°C").is_err());
+    assert!(UnitSelection::parse("nautical").is_err());
+    assert!(UnitSelection::parse("x:kn").is_err());
+}
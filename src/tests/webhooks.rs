@@ -0,0 +1,77 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+use crate::delivery::{generate_webhook_secret, retry_delay, send_delivery, sign_payload, EVENT_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+#[test]
+fn test_sign_payload() {
+    assert_eq!(
+        sign_payload("whsec_test", 1700000000, br#"{"a":1}"#),
+        "38877139021993b830af32feea6e18a8da83eb2f6e49ee50bd9e4cf4ca4d3789"
+    );
+}
+
+#[test]
+fn test_generated_webhook_secrets() {
+    let first = generate_webhook_secret();
+
+    assert!(first.starts_with("whsec_"));
+    assert_eq!(first.len(), "whsec_".len() + 64);
+    assert_ne!(first, generate_webhook_secret());
+}
+
+#[test]
+fn test_retry_delay() {
+    let base = Duration::from_secs(10);
+
+    assert_eq!(retry_delay(1, base), Duration::from_secs(10));
+    assert_eq!(retry_delay(2, base), Duration::from_secs(20));
+    assert_eq!(retry_delay(4, base), Duration::from_secs(80));
+    assert_eq!(retry_delay(30, base), Duration::from_secs(60 * 60));
+}
+
+type Received = Arc<Mutex<Vec<(String, String, String, Vec<u8>)>>>;
+
+async fn receive(req: HttpRequest, body: web::Bytes, received: web::Data<Received>) -> HttpResponse {
+    let header = |name: &str| req.headers().get(name).and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+    received.lock().unwrap().push((header(EVENT_HEADER), header(TIMESTAMP_HEADER), header(SIGNATURE_HEADER), body.to_vec()));
+
+    if req.path() == "/fail" {
+        HttpResponse::InternalServerError().finish()
+    } else {
+        HttpResponse::NoContent().finish()
+    }
+}
+
+#[actix_web::test]
+async fn test_send_delivery_to_local_receiver() {
+    let received: Received = Arc::default();
+    let data = web::Data::new(received.clone());
+
+    let server = HttpServer::new(move || App::new().app_data(data.clone()).default_service(web::post().to(receive)))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let addr = server.addrs()[0];
+    let handle = server.run();
+    let server_handle = handle.handle();
+    actix_web::rt::spawn(handle);
+
+    let client = reqwest::Client::new();
+    let body = br#"{"measurements":[]}"#.to_vec();
+
+    let ok = send_delivery(&client, &format!("http://{}/hook", addr), "whsec_test", 1, "measurement.created", body.clone()).await;
+    let failed = send_delivery(&client, &format!("http://{}/fail", addr), "whsec_test", 2, "measurement.created", body.clone()).await;
+    server_handle.stop(true).await;
+    let unreachable = send_delivery(&client, &format!("http://{}/hook", addr), "whsec_test", 3, "measurement.created", body.clone()).await;
+
+    assert_eq!(ok, Ok(204));
+    assert_eq!(failed, Ok(500));
+    assert!(unreachable.is_err());
+
+    let received = received.lock().unwrap();
+    let (event, timestamp, signature, payload) = &received[0];
+    assert_eq!(event, "measurement.created");
+    assert_eq!(payload, &body);
+    assert_eq!(signature, &format!("sha256={}", sign_payload("whsec_test", timestamp.parse().unwrap(), &body)));
+}
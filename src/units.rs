@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+/// Decimal places kept after converting a value.
+const CONVERTED_SCALE: i64 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Temperature,
+    Speed,
+    Pressure,
+    Length,
+    Ratio,
+}
+
+/// Units a measurement type may declare. Values of one dimension convert into each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+    MetersPerSecond,
+    KilometersPerHour,
+    Knots,
+    MilesPerHour,
+    Hectopascal,
+    MillimetersOfMercury,
+    InchesOfMercury,
+    Millimeters,
+    Inches,
+    Percent,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 13] = [
+        Unit::Celsius,
+        Unit::Fahrenheit,
+        Unit::Kelvin,
+        Unit::MetersPerSecond,
+        Unit::KilometersPerHour,
+        Unit::Knots,
+        Unit::MilesPerHour,
+        Unit::Hectopascal,
+        Unit::MillimetersOfMercury,
+        Unit::InchesOfMercury,
+        Unit::Millimeters,
+        Unit::Inches,
+        Unit::Percent,
+    ];
+
+    /// Canonical symbol, stored in `measurements_type.units`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Unit::Celsius => "°C",
+            Unit::Fahrenheit => "°F",
+            Unit::Kelvin => "K",
+            Unit::MetersPerSecond => "m/s",
+            Unit::KilometersPerHour => "km/h",
+            Unit::Knots => "kn",
+            Unit::MilesPerHour => "mph",
+            Unit::Hectopascal => "hPa",
+            Unit::MillimetersOfMercury => "mmHg",
+            Unit::InchesOfMercury => "inHg",
+            Unit::Millimeters => "mm",
+            Unit::Inches => "in",
+            Unit::Percent => "%",
+        }
+    }
+
+    /// Other spellings accepted for the unit, matched case-insensitively.
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            Unit::Celsius => &["C", "degC", "celsius"],
+            Unit::Fahrenheit => &["F", "degF", "fahrenheit"],
+            Unit::Kelvin => &["kelvin"],
+            Unit::MetersPerSecond => &["m s-1", "mps"],
+            Unit::KilometersPerHour => &["kmh", "kph"],
+            Unit::Knots => &["kt", "kts", "knots"],
+            Unit::MilesPerHour => &["mi/h"],
+            Unit::Hectopascal => &["mbar", "mb"],
+            Unit::MillimetersOfMercury => &["mm Hg", "torr"],
+            Unit::InchesOfMercury => &["in Hg"],
+            Unit::Millimeters => &["millimeters"],
+            Unit::Inches => &["inch", "inches"],
+            Unit::Percent => &["percent", "pct"],
+        }
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        match self {
+            Unit::Celsius | Unit::Fahrenheit | Unit::Kelvin => Dimension::Temperature,
+            Unit::MetersPerSecond | Unit::KilometersPerHour | Unit::Knots | Unit::MilesPerHour => Dimension::Speed,
+            Unit::Hectopascal | Unit::MillimetersOfMercury | Unit::InchesOfMercury => Dimension::Pressure,
+            Unit::Millimeters | Unit::Inches => Dimension::Length,
+            Unit::Percent => Dimension::Ratio,
+        }
+    }
+
+    /// `(offset, numerator, denominator)` such that a value in the base unit of the dimension
+    /// (°C, m/s, hPa, mm) is `(value + offset) * numerator / denominator`.
+    fn base_factors(&self) -> (&'static str, i64, i64) {
+        match self {
+            Unit::Celsius => ("0", 1, 1),
+            Unit::Fahrenheit => ("-32", 5, 9),
+            Unit::Kelvin => ("-273.15", 1, 1),
+            Unit::MetersPerSecond => ("0", 1, 1),
+            Unit::KilometersPerHour => ("0", 5, 18),
+            Unit::Knots => ("0", 463, 900),
+            Unit::MilesPerHour => ("0", 44704, 100000),
+            Unit::Hectopascal => ("0", 1, 1),
+            Unit::MillimetersOfMercury => ("0", 133322387415, 100000000000),
+            Unit::InchesOfMercury => ("0", 3386388640341, 100000000000),
+            Unit::Millimeters => ("0", 1, 1),
+            Unit::Inches => ("0", 254, 10),
+            Unit::Percent => ("0", 1, 1),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Unit {
+    type Err = ApiError;
+
+    fn from_str(units: &str) -> Result<Self, Self::Err> {
+        let units = units.trim();
+        Unit::ALL
+            .into_iter()
+            .find(|unit| unit.as_str() == units || unit.aliases().iter().any(|alias| alias.eq_ignore_ascii_case(units)))
+            .ok_or_else(|| {
+                let known: Vec<&str> = Unit::ALL.iter().map(Unit::as_str).collect();
+                ApiError::Validation(format!("unknown units {}, expected one of {}", units, known.join(", ")))
+            })
+    }
+}
+
+/// Linear conversion `value * scale + offset` between two units of one dimension.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    scale: BigDecimal,
+    offset: BigDecimal,
+    target: Unit,
+}
+
+impl Conversion {
+    pub fn between(from: Unit, to: Unit) -> Option<Conversion> {
+        if from.dimension() != to.dimension() {
+            return None;
+        }
+
+        let (from_offset, from_num, from_den) = from.base_factors();
+        let (to_offset, to_num, to_den) = to.base_factors();
+        let from_offset = BigDecimal::from_str(from_offset).ok()?;
+        let to_offset = BigDecimal::from_str(to_offset).ok()?;
+
+        // The mercury factors are too large to multiply as i64.
+        let scale = BigDecimal::from(from_num) * BigDecimal::from(to_den) / (BigDecimal::from(from_den) * BigDecimal::from(to_num));
+        let offset = &from_offset * &scale - to_offset;
+
+        Some(Conversion { scale, offset, target: to })
+    }
+
+    /// Unit the converted values are in.
+    pub fn target(&self) -> Unit {
+        self.target
+    }
+
+    pub fn apply(&self, value: &BigDecimal) -> BigDecimal {
+        (value * &self.scale + &self.offset).round(CONVERTED_SCALE).normalized()
+    }
+
+    /// Converts the sum of `count` values.
+    pub fn apply_sum(&self, sum: &BigDecimal, count: i64) -> BigDecimal {
+        (sum * &self.scale + &self.offset * BigDecimal::from(count)).round(CONVERTED_SCALE).normalized()
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UnitSystem {
+    /// °C, m/s, hPa and mm.
+    Metric,
+    /// °F, mph, inHg and in.
+    Imperial,
+}
+
+impl UnitSystem {
+    pub fn unit(&self, dimension: Dimension) -> Option<Unit> {
+        match (self, dimension) {
+            (_, Dimension::Ratio) => None,
+            (UnitSystem::Metric, Dimension::Temperature) => Some(Unit::Celsius),
+            (UnitSystem::Metric, Dimension::Speed) => Some(Unit::MetersPerSecond),
+            (UnitSystem::Metric, Dimension::Pressure) => Some(Unit::Hectopascal),
+            (UnitSystem::Metric, Dimension::Length) => Some(Unit::Millimeters),
+            (UnitSystem::Imperial, Dimension::Temperature) => Some(Unit::Fahrenheit),
+            (UnitSystem::Imperial, Dimension::Speed) => Some(Unit::MilesPerHour),
+            (UnitSystem::Imperial, Dimension::Pressure) => Some(Unit::InchesOfMercury),
+            (UnitSystem::Imperial, Dimension::Length) => Some(Unit::Inches),
+        }
+    }
+}
+
+/// Target units requested with `?units=`: a unit system, `type_id:unit` pairs, or both,
+/// separated by commas, e.g. `imperial,3:kn`. Pairs take precedence over the system.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UnitSelection {
+    pub system: Option<UnitSystem>,
+    pub types: HashMap<i32, Unit>,
+}
+
+impl UnitSelection {
+    pub fn parse(selection: &str) -> Result<UnitSelection, ApiError> {
+        let mut parsed = UnitSelection::default();
+
+        for item in selection.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.split_once(':') {
+                Some((type_id, unit)) => {
+                    let type_id = type_id
+                        .trim()
+                        .parse()
+                        .map_err(|_| ApiError::Validation(format!("invalid measurement type ID in units {}", item)))?;
+                    parsed.types.insert(type_id, unit.parse()?);
+                }
+                None => {
+                    parsed.system = Some(match item {
+                        "metric" => UnitSystem::Metric,
+                        "imperial" => UnitSystem::Imperial,
+                        _ => return Err(ApiError::Validation(format!("unknown unit system {}", item))),
+                    });
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Conversion of values of type `type_id`, stored in `units`, or `None` to leave them as
+    /// stored. Units requested for the type that it cannot be converted to are an error.
+    pub fn conversion(&self, type_id: i32, units: &str) -> Result<Option<Conversion>, ApiError> {
+        let requested = self.types.get(&type_id).copied();
+        let stored = match (units.parse::<Unit>(), requested) {
+            (Ok(stored), _) => stored,
+            (Err(_), None) => return Ok(None),
+            (Err(_), Some(target)) => {
+                return Err(ApiError::Validation(format!(
+                    "cannot convert measurement type {} from {} to {}",
+                    type_id, units, target
+                )))
+            }
+        };
+
+        let target = match requested.or_else(|| self.system.and_then(|system| system.unit(stored.dimension()))) {
+            Some(target) => target,
+            None => return Ok(None),
+        };
+
+        Conversion::between(stored, target).map(Some).ok_or_else(|| {
+            ApiError::Validation(format!("cannot convert measurement type {} from {} to {}", type_id, stored, target))
+        })
+    }
+}